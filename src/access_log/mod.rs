@@ -0,0 +1,153 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use log::info;
+use serde::Serialize;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// log target of access records, can be routed to its own appender in log4rs config
+const ACCESS_LOG_TARGET: &str = "access";
+
+#[derive(Debug, Serialize)]
+struct AccessRecord {
+    request_id: String,
+    method: String,
+    path: String,
+    route: Option<String>,
+    status: u16,
+    latency_ms: f64,
+    bytes: Option<u64>,
+    peer: Option<String>,
+}
+
+/// incoming `X-Request-ID` is kept if it is a valid header value, otherwise a new one is generated
+fn request_id(headers: &HeaderMap) -> HeaderValue {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .filter(|v| !v.is_empty() && v.to_str().is_ok())
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&Uuid::new_v4().to_string()).expect("uuid is a valid header")
+        })
+}
+
+/// has to be layered on the router with DSL routes to see matched route
+pub async fn access_log(mut request: Request, next: Next) -> Response {
+    let start = Instant::now();
+
+    let request_id = request_id(request.headers());
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id.clone());
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string());
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0.to_string());
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id.clone());
+
+    let record = AccessRecord {
+        request_id: request_id.to_str().unwrap_or_default().to_string(),
+        method,
+        path,
+        route,
+        status: response.status().as_u16(),
+        latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+        // streamed bodies have no exact size
+        bytes: response.body().size_hint().exact(),
+        peer,
+    };
+    if let Ok(line) = serde_json::to_string(&record) {
+        info!(target: ACCESS_LOG_TARGET, "{}", line);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{
+        Router,
+        body::Body,
+        http::{HeaderMap, HeaderValue, Request},
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    use crate::access_log::{REQUEST_ID_HEADER, access_log, request_id};
+
+    #[test]
+    fn test_request_id() {
+        let mut headers = HeaderMap::new();
+        let generated = request_id(&headers);
+        assert_eq!(generated.len(), 36);
+
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static("abc-1"));
+        assert_eq!(request_id(&headers), "abc-1");
+
+        headers.insert(REQUEST_ID_HEADER, HeaderValue::from_static(""));
+        assert_ne!(request_id(&headers), "");
+    }
+
+    #[tokio::test]
+    async fn test_access_log() {
+        let app = Router::new()
+            .route(
+                "/items/{id}",
+                get(|headers: HeaderMap| async move {
+                    headers
+                        .get(REQUEST_ID_HEADER)
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_string()
+                }),
+            )
+            .layer(axum::middleware::from_fn(access_log));
+
+        let req = Request::builder()
+            .uri("/items/1")
+            .header(REQUEST_ID_HEADER, "from-client")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(
+            resp.headers().get(REQUEST_ID_HEADER).unwrap(),
+            "from-client"
+        );
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "from-client");
+
+        let req = Request::builder()
+            .uri("/items/1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        let generated = resp.headers().get(REQUEST_ID_HEADER).unwrap().clone();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, generated.as_bytes());
+    }
+}
@@ -0,0 +1,258 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::args::types::{Args, GraphFormat};
+use crate::endpoints::reload::ReloadableRouter;
+use crate::engine::graph::FlowGraph;
+use crate::metrics::metrics_handler;
+
+/// admin API is served on its own listener, so it is never exposed together with DSL routes
+pub struct AdminState {
+    args: Args,
+    reloadable: Arc<ReloadableRouter>,
+    ready: AtomicBool,
+}
+
+impl AdminState {
+    pub fn new(args: &Args, reloadable: Arc<ReloadableRouter>) -> Arc<Self> {
+        Arc::new(Self {
+            args: args.clone(),
+            reloadable,
+            ready: AtomicBool::new(false),
+        })
+    }
+
+    /// main listener is bound and accepts requests
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::Relaxed);
+    }
+}
+
+async fn routes(State(state): State<Arc<AdminState>>) -> Response {
+    Json(state.reloadable.routes().as_ref()).into_response()
+}
+
+#[derive(Deserialize)]
+struct GraphQuery {
+    method: String,
+    route: String,
+    #[serde(default)]
+    format: GraphFormat,
+}
+
+/// flow diagram of a loaded endpoint, e.g. `/graph?method=GET&route=/users/{id}`
+async fn graph(State(state): State<Arc<AdminState>>, Query(query): Query<GraphQuery>) -> Response {
+    let method = query.method.to_uppercase();
+    let Some(collection) = state.reloadable.collection() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+    let Some((endpoint, _)) = collection.find(&method, &query.route) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": format!("no endpoint for {} {}", method, query.route)})),
+        )
+            .into_response();
+    };
+
+    FlowGraph::from_endpoint(endpoint, &state.args.dsl_path)
+        .render(query.format)
+        .into_response()
+}
+
+/// DSL dir is rebuilt on a blocking thread, the admin API keeps serving meanwhile
+async fn reload(State(state): State<Arc<AdminState>>) -> Response {
+    match state.reloadable.reload().await {
+        Ok(()) => {
+            info!("DSL dir reloaded from admin API");
+            Json(json!({"status": "reloaded"})).into_response()
+        }
+        Err(e) => {
+            warn!("DSL reload from admin API failed: {}", e);
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({"status": "failed", "error": e})),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn live() -> Response {
+    Json(json!({"status": "ok"})).into_response()
+}
+
+async fn ready(State(state): State<Arc<AdminState>>) -> Response {
+    if !state.ready.load(Ordering::Relaxed) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"status": "starting"})),
+        )
+            .into_response();
+    }
+
+    Json(json!({"status": "ok"})).into_response()
+}
+
+async fn config(State(state): State<Arc<AdminState>>) -> Response {
+    Json(&state.args).into_response()
+}
+
+pub fn admin_router(state: Arc<AdminState>) -> Router {
+    Router::new()
+        .route("/routes", get(routes))
+        .route("/graph", get(graph))
+        .route("/reload", post(reload))
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .route("/config", get(config))
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+}
+
+pub async fn serve_admin(state: Arc<AdminState>) {
+    let Some(port) = state.args.admin_port else {
+        return;
+    };
+
+    let addr = format!("{}:{}", state.args.admin_bind, port);
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            warn!("cannot start admin API at {}: {}", addr, e);
+            return;
+        }
+    };
+
+    info!("Starting admin API at http://{}", addr);
+    if let Err(e) = axum::serve(listener, admin_router(state)).await {
+        warn!("{}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, write};
+
+    use axum::{
+        body::{Body, to_bytes},
+        http::Request,
+    };
+    use clap::Parser;
+    use serde_json::{Value as JsonValue, json};
+    use tower::ServiceExt;
+
+    use crate::admin::{AdminState, admin_router};
+    use crate::args::types::Args;
+    use crate::endpoints::reload::ReloadableRouter;
+
+    async fn call(router: axum::Router, method: &str, uri: &str) -> (u16, JsonValue) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.oneshot(req).await.unwrap();
+        let status = resp.status().as_u16();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_admin_router() {
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            "./unittest_dsl",
+            "--disable-swagger",
+            "--admin-port",
+            "8091",
+        ]);
+        let state = AdminState::new(&args, ReloadableRouter::new(&args));
+        let router = admin_router(state.clone());
+
+        let (status, routes) = call(router.clone(), "GET", "/routes").await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            routes[2],
+            json!({
+                "method": "GET",
+                "url": "/test/test/endp",
+                "tag": "/test",
+                "guards": ["./unittest_dsl/test/GET/.guard.yml", "./unittest_dsl/.guard"],
+                "source": "./unittest_dsl/test/GET/test/endp.yml",
+            })
+        );
+        assert_eq!(routes.as_array().unwrap().len(), 3);
+
+        let (status, _) = call(router.clone(), "GET", "/health/live").await;
+        assert_eq!(status, 200);
+
+        let (status, _) = call(router.clone(), "GET", "/health/ready").await;
+        assert_eq!(status, 503);
+        state.set_ready();
+        let (status, _) = call(router.clone(), "GET", "/health/ready").await;
+        assert_eq!(status, 200);
+
+        let (status, body) = call(router.clone(), "POST", "/reload").await;
+        assert_eq!(status, 200);
+        assert_eq!(body, json!({"status": "reloaded"}));
+
+        let (status, _) = call(router.clone(), "GET", "/graph?method=GET&route=/nope").await;
+        assert_eq!(status, 404);
+
+        let req = Request::builder()
+            .uri("/graph?method=get&route=/test/test/endp&format=dot")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).starts_with("digraph flow {"));
+
+        let (status, config) = call(router, "GET", "/config").await;
+        assert_eq!(status, 200);
+        assert_eq!(config["dsl_path"], "./unittest_dsl");
+        assert_eq!(config["admin_port"], 8091);
+    }
+
+    #[tokio::test]
+    async fn test_admin_reload_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        create_dir_all(dir.join("api/GET")).unwrap();
+        write(dir.join("api/GET/hello.yml"), "test:\n  return: one\n").unwrap();
+
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            dir.to_str().unwrap(),
+            "--disable-swagger",
+        ]);
+        let state = AdminState::new(&args, ReloadableRouter::new(&args));
+        let router = admin_router(state);
+
+        write(dir.join("api/GET/hello.yml"), "test: [").unwrap();
+        let (reloaded, live) = tokio::join!(
+            call(router.clone(), "POST", "/reload"),
+            call(router.clone(), "GET", "/health/live")
+        );
+        assert_eq!(reloaded.0, 422);
+        assert_eq!(reloaded.1["status"], "failed");
+        assert_eq!(live.0, 200);
+
+        let (status, routes) = call(router, "GET", "/routes").await;
+        assert_eq!(status, 200);
+        assert_eq!(routes.as_array().unwrap().len(), 1);
+    }
+}
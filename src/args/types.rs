@@ -1,4 +1,9 @@
-use clap::Parser;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::limits::{DEFAULT_MAX_STEPS, parse_duration};
 
 fn validate_bind_address(addr: &str) -> Result<String, String> {
     if addr.parse::<std::net::IpAddr>().is_ok() {
@@ -17,6 +22,15 @@ fn validate_log_config(path: &str) -> Result<String, String> {
     }
 }
 
+fn validate_openapi_meta(path: &str) -> Result<String, String> {
+    let path_obj = std::path::Path::new(path);
+    if path_obj.exists() && path_obj.is_file() {
+        Ok(path.to_string())
+    } else {
+        Err(format!("OpenAPI metadata file does not exist: {}", path))
+    }
+}
+
 fn validate_dsl_path(path: &str) -> Result<String, String> {
     let path_obj = std::path::Path::new(path);
     if path_obj.exists() && path_obj.is_dir() {
@@ -26,7 +40,112 @@ fn validate_dsl_path(path: &str) -> Result<String, String> {
     }
 }
 
-#[derive(Parser, Debug, Clone)]
+/// how requests are checked against `declare` allowlists
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestValidation {
+    /// allowlists are used for OpenAPI only
+    #[default]
+    Off,
+    /// invalid requests are rejected with 400
+    Reject,
+    /// unknown params and body fields are removed, other violations are rejected
+    Strip,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum SpecFormat {
+    Json,
+    Yaml,
+}
+
+impl SpecFormat {
+    /// json unless file has yaml extension
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".yml") || path.ends_with(".yaml") {
+            return Self::Yaml;
+        }
+        Self::Json
+    }
+}
+
+/// flow diagram language
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphFormat {
+    #[default]
+    Mermaid,
+    /// Graphviz
+    Dot,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// write generated OpenAPI document to a file without starting the server
+    Openapi {
+        /// output file
+        #[arg(short, long)]
+        output: String,
+
+        /// document format, guessed from output file extension if not set
+        #[arg(short, long, value_enum)]
+        format: Option<SpecFormat>,
+    },
+
+    /// create DSL endpoint skeletons from OpenAPI 3 document
+    Scaffold {
+        /// OpenAPI document, JSON or YAML
+        spec: String,
+
+        /// DSL dir to create endpoints in, DSL path by default
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// overwrite existing endpoint files
+        #[arg(long, action)]
+        force: bool,
+    },
+
+    /// check DSL dir for broken flows and conflicting routes, fails on errors
+    Validate,
+
+    /// run one endpoint with its guards without starting the server, prints status and response
+    Run {
+        /// HTTP method, e.g. GET
+        method: String,
+
+        /// request path, e.g. /users/42
+        route: String,
+
+        /// JSON file with `headers`, `params` and `body` of the request, stdin if not set
+        #[arg(short, long)]
+        input: Option<String>,
+    },
+
+    /// run `*.test.yml` suites with mocked upstreams, fails if any case fails
+    Test {
+        /// suite file or dir to look for suites in, DSL path by default
+        path: Option<String>,
+    },
+
+    /// print flow diagram of an endpoint with its guards and templates
+    Graph {
+        /// HTTP method, e.g. GET
+        method: String,
+
+        /// endpoint url, e.g. /users/{id}, or request path
+        route: String,
+
+        #[arg(short, long, value_enum, default_value_t = GraphFormat::Mermaid)]
+        format: GraphFormat,
+
+        /// output file, stdout if not set
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Parser, Debug, Clone, Serialize)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// The port to run the server on
@@ -49,6 +168,70 @@ pub struct Args {
     /// disable swagger (enabled by default)
     #[arg(short = 'D', long, env, action)]
     pub disable_swagger: bool,
+
+    /// return bare values instead of {"response": ...} envelope (envelope is enabled by default)
+    #[arg(short = 'W', long, env, action)]
+    pub no_wrapper: bool,
+
+    /// watch DSL path and reload endpoints on change (disabled by default)
+    #[arg(short = 'R', long, env, action)]
+    pub hot_reload: bool,
+
+    /// The port to run admin API on (disabled if not set)
+    #[arg(short, long, env, default_value = None, value_parser = clap::value_parser!(u16).range(1..65535))]
+    pub admin_port: Option<u16>,
+
+    /// Bind address for admin API
+    #[arg(long, env, default_value = "127.0.0.1", value_parser = validate_bind_address)]
+    pub admin_bind: String,
+
+    /// OTLP/HTTP collector to export traces to, e.g. http://localhost:4318 (disabled if not set)
+    #[arg(long, env, default_value = None)]
+    pub otlp_endpoint: Option<String>,
+
+    /// check requests against `declare` allowlists before guards
+    #[arg(long, env, value_enum, default_value_t = RequestValidation::Off)]
+    pub validate_requests: RequestValidation,
+
+    /// development mode: returned values are checked against declared responses, mismatches are logged
+    #[arg(long, env, action)]
+    pub dev_mode: bool,
+
+    /// tasks one request may execute before the flow is aborted, stops cyclic `next` chains
+    #[arg(long, env, default_value_t = DEFAULT_MAX_STEPS)]
+    pub max_steps: usize,
+
+    /// time limit of a flow, e.g. `30s` or `500ms`. `timeout` of `declare` task overrides it
+    #[arg(long, env, default_value = None, value_parser = parse_duration)]
+    pub flow_timeout: Option<Duration>,
+
+    /// refuse to start or hot reload when DSL validation finds errors
+    #[arg(long, env, action)]
+    pub strict: bool,
+
+    /// OpenAPI metadata file (`.openapi.yml` in the root of DSL path by default)
+    #[arg(long, env, default_value = None, value_parser = validate_openapi_meta)]
+    pub openapi_meta: Option<String>,
+
+    /// OpenAPI title, overrides metadata file
+    #[arg(long, env)]
+    pub api_title: Option<String>,
+
+    /// OpenAPI version, overrides metadata file
+    #[arg(long, env)]
+    pub api_version: Option<String>,
+
+    /// OpenAPI description, overrides metadata file
+    #[arg(long, env)]
+    pub api_description: Option<String>,
+
+    /// OpenAPI server url, can be repeated. Overrides metadata file
+    #[arg(long, env, value_delimiter = ',')]
+    pub api_server: Vec<String>,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
 }
 
 pub fn get_args() -> Args {
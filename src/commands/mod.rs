@@ -0,0 +1,270 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use log::info;
+
+use crate::args::types::{Args, Command, GraphFormat, SpecFormat};
+use crate::commands::run::run_endpoint;
+use crate::commands::suite::run_suites;
+use crate::endpoints::openapi::{ApiMetadata, build_spec};
+use crate::endpoints::parser::EndpointsCollection;
+use crate::endpoints::scaffold::generate_skeletons;
+use crate::engine::graph::FlowGraph;
+use crate::engine::lint::lint_dsl;
+
+mod run;
+mod suite;
+
+/// subcommands work with DSL dir and exit without starting the server
+pub fn run_command(args: &Args, command: &Command) -> ExitCode {
+    let result = match command {
+        Command::Openapi { output, format } => export_openapi(args, output, *format),
+        Command::Scaffold {
+            spec,
+            output,
+            force,
+        } => scaffold(spec, output.as_ref().unwrap_or(&args.dsl_path), *force),
+        Command::Validate => validate(&args.dsl_path),
+        Command::Run {
+            method,
+            route,
+            input,
+        } => run_endpoint(args, method, route, input.as_deref()).map(|o| println!("{}", o)),
+        Command::Test { path } => run_suites(args, path.as_ref().unwrap_or(&args.dsl_path)),
+        Command::Graph {
+            method,
+            route,
+            format,
+            output,
+        } => export_graph(args, method, route, *format, output.as_deref()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn render_openapi(args: &Args, format: SpecFormat) -> Result<String, String> {
+    // metadata errors are fatal here unlike in the server
+    let metadata = ApiMetadata::load(args)?;
+    let collection = EndpointsCollection::parse_from_dir(&args.dsl_path);
+    let spec = build_spec(&collection, metadata);
+
+    match format {
+        SpecFormat::Json => serde_json::to_string_pretty(&spec).map_err(|e| e.to_string()),
+        SpecFormat::Yaml => serde_yaml_ng::to_string(&spec).map_err(|e| e.to_string()),
+    }
+}
+
+fn export_openapi(args: &Args, output: &str, format: Option<SpecFormat>) -> Result<(), String> {
+    let format = format.unwrap_or_else(|| SpecFormat::from_path(output));
+    let content = render_openapi(args, format)?;
+    write(output, content).map_err(|e| format!("{}: {}", output, e))?;
+
+    info!("OpenAPI document written to {}", output);
+    Ok(())
+}
+
+/// existing endpoint files are kept unless `force` is set
+fn scaffold(spec: &str, output: &str, force: bool) -> Result<(), String> {
+    let content = read_to_string(spec).map_err(|e| format!("{}: {}", spec, e))?;
+    let spec_yml = serde_yaml_ng::from_str(&content).map_err(|e| format!("{}: {}", spec, e))?;
+    let files = generate_skeletons(&spec_yml).map_err(|e| format!("{}: {}", spec, e))?;
+
+    let mut created = 0;
+    for file in files {
+        let path = PathBuf::from(output).join(&file.path);
+        if path.exists() && !force {
+            info!("{} already exists, skipping", path.display());
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        write(&path, file.content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        created += 1;
+    }
+
+    info!("{} endpoint files created in {}", created, output);
+    Ok(())
+}
+
+fn render_graph(
+    args: &Args,
+    method: &str,
+    route: &str,
+    format: GraphFormat,
+) -> Result<String, String> {
+    let method = method.to_uppercase();
+    let collection = EndpointsCollection::parse_from_dir(&args.dsl_path);
+    let (endpoint, _) = collection
+        .find(&method, route)
+        .ok_or_else(|| format!("no endpoint for {} {}", method, route))?;
+
+    Ok(FlowGraph::from_endpoint(endpoint, &args.dsl_path).render(format))
+}
+
+fn export_graph(
+    args: &Args,
+    method: &str,
+    route: &str,
+    format: GraphFormat,
+    output: Option<&str>,
+) -> Result<(), String> {
+    let graph = render_graph(args, method, route, format)?;
+    match output {
+        Some(output) => write(output, graph).map_err(|e| format!("{}: {}", output, e)),
+        None => {
+            println!("{}", graph);
+            Ok(())
+        }
+    }
+}
+
+/// issues go to stdout, warnings alone do not fail
+fn validate(dsl_path: &str) -> Result<(), String> {
+    let issues = lint_dsl(dsl_path);
+    issues.iter().for_each(|i| println!("{}", i));
+
+    let errors = issues.iter().filter(|i| i.is_error()).count();
+    println!("{} errors, {} warnings", errors, issues.len() - errors);
+
+    match errors {
+        0 => Ok(()),
+        n => Err(format!("DSL validation failed with {} errors", n)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::ExitCode;
+
+    use clap::Parser;
+
+    use crate::args::types::{Args, GraphFormat, SpecFormat};
+    use crate::commands::{render_graph, render_openapi, run_command, scaffold, validate};
+
+    #[test]
+    fn test_render_openapi() {
+        let args = Args::parse_from(["rstrouter", "--dsl-path", "./unittest_dsl"]);
+
+        let json = render_openapi(&args, SpecFormat::Json).unwrap();
+        let spec: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(spec["info"]["title"], "rstrouter");
+
+        let yaml = render_openapi(&args, SpecFormat::Yaml).unwrap();
+        let spec: serde_yaml_ng::Value = serde_yaml_ng::from_str(&yaml).unwrap();
+        assert_eq!(spec["info"]["title"], "rstrouter");
+    }
+
+    #[test]
+    fn test_export_openapi() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("spec.yaml");
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            "./unittest_dsl",
+            "--api-title",
+            "Exported",
+            "openapi",
+            "--output",
+            output.to_str().unwrap(),
+        ]);
+
+        let code = run_command(&args, args.command.as_ref().unwrap());
+        assert_eq!(code, ExitCode::SUCCESS);
+
+        let content = std::fs::read_to_string(&output).unwrap();
+        let spec: serde_yaml_ng::Value = serde_yaml_ng::from_str(&content).unwrap();
+        assert_eq!(spec["info"]["title"], "Exported");
+
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            "./unittest_dsl",
+            "openapi",
+            "--output",
+            "/not/existing/dir/spec.json",
+        ]);
+        let code = run_command(&args, args.command.as_ref().unwrap());
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn test_spec_format_from_path() {
+        assert_eq!(SpecFormat::from_path("spec.yaml"), SpecFormat::Yaml);
+        assert_eq!(SpecFormat::from_path("spec.yml"), SpecFormat::Yaml);
+        assert_eq!(SpecFormat::from_path("spec.json"), SpecFormat::Json);
+        assert_eq!(SpecFormat::from_path("spec"), SpecFormat::Json);
+    }
+
+    #[test]
+    fn test_scaffold() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = dir.path().join("spec.json");
+        std::fs::write(
+            &spec,
+            r#"{"openapi": "3.1.0", "paths": {"/ping": {"get": {"responses": {"200": {}}}}}}"#,
+        )
+        .unwrap();
+        let output = dir.path().join("dsl");
+        let endpoint = output.join("GET/ping.yml");
+
+        scaffold(spec.to_str().unwrap(), output.to_str().unwrap(), false).unwrap();
+        assert!(endpoint.is_file());
+
+        std::fs::write(&endpoint, "custom: {return: ok}").unwrap();
+        scaffold(spec.to_str().unwrap(), output.to_str().unwrap(), false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&endpoint).unwrap(),
+            "custom: {return: ok}"
+        );
+
+        scaffold(spec.to_str().unwrap(), output.to_str().unwrap(), true).unwrap();
+        assert!(
+            std::fs::read_to_string(&endpoint)
+                .unwrap()
+                .contains("reflect.mock")
+        );
+
+        assert!(scaffold("./not_exists.json", output.to_str().unwrap(), false).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("./unittest_dsl").is_ok());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("GET")).unwrap();
+        std::fs::write(
+            dir.join("GET/broken.yml"),
+            "task:
+  some: shape
+",
+        )
+        .unwrap();
+
+        let args = Args::parse_from(["rstrouter", "--dsl-path", dir.to_str().unwrap(), "validate"]);
+        let code = run_command(&args, args.command.as_ref().unwrap());
+        assert_eq!(code, ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn test_render_graph() {
+        let args = Args::parse_from(["rstrouter", "--dsl-path", "./unittest_dsl"]);
+
+        let dot = render_graph(&args, "post", "/test/endp", GraphFormat::Dot).unwrap();
+        assert!(dot.starts_with("digraph flow {"));
+        assert!(dot.contains(r#"t0 [label="return\nreturn"];"#), "{}", dot);
+
+        assert!(render_graph(&args, "GET", "/not/exists", GraphFormat::Mermaid).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::{IsTerminal, Read, stdin};
+use std::sync::Arc;
+
+use axum::body::{Bytes, to_bytes};
+use axum::http::{header, response::Parts};
+use axum::response::IntoResponse;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::args::types::Args;
+use crate::endpoints::parser::{Endpoint, EndpointsCollection, RouteMatch};
+use crate::endpoints::types::Request;
+use crate::engine::{Engine, EngineConfig};
+
+/// request passed to `run`, every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RequestInput {
+    pub headers: HashMap<String, String>,
+    pub params: HashMap<String, String>,
+    pub body: JsonValue,
+}
+
+impl RequestInput {
+    /// stdin is read only when something is piped into it
+    fn load(input: Option<&str>) -> Result<Self, String> {
+        let content = match input {
+            Some("-") | None if !stdin().is_terminal() => {
+                let mut content = String::new();
+                stdin()
+                    .read_to_string(&mut content)
+                    .map_err(|e| format!("stdin: {}", e))?;
+                content
+            }
+            Some("-") | None => return Ok(Self::default()),
+            Some(path) => read_to_string(path).map_err(|e| format!("{}: {}", path, e))?,
+        };
+
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(&content).map_err(|e| format!("request: {}", e))
+    }
+
+    fn into_request(self, method: &str, endpoint: &Endpoint, path: RouteMatch) -> Request {
+        // axum gives lowercase header names
+        let headers = self
+            .headers
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), v))
+            .collect();
+
+        Request::new(headers, self.body, self.params).with_route(
+            method,
+            &endpoint.url_path,
+            &path.raw_path,
+            path.params,
+        )
+    }
+}
+
+/// endpoint serving the route and the request for it
+pub fn prepare_request<'a>(
+    collection: &'a EndpointsCollection,
+    method: &str,
+    route: &str,
+    input: RequestInput,
+) -> Result<(&'a Endpoint, Request), String> {
+    let method = method.to_uppercase();
+    let (endpoint, route_match) = collection
+        .find(&method, route)
+        .ok_or_else(|| format!("no endpoint for {} {}", method, route))?;
+
+    Ok((endpoint, input.into_request(&method, endpoint, route_match)))
+}
+
+/// response as the client would get it, streams are read to the end
+pub async fn execute(engine: Engine, request: Request) -> Result<(Parts, Bytes), String> {
+    let response = if engine.is_streaming() {
+        Arc::new(engine).execute_stream(request).await
+    } else {
+        engine.execute(request).await.into_response()
+    };
+
+    let (parts, body) = response.into_parts();
+    let bytes = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| format!("cannot read response: {}", e))?;
+
+    Ok((parts, bytes))
+}
+
+/// status line, headers and body
+fn format_response(parts: &Parts, bytes: &Bytes) -> Result<String, String> {
+    let mut output = format!("{}\n", parts.status);
+    for (k, v) in &parts.headers {
+        output.push_str(&format!(
+            "{}: {}\n",
+            k,
+            String::from_utf8_lossy(v.as_bytes())
+        ));
+    }
+
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    let body = match serde_json::from_slice::<JsonValue>(bytes) {
+        Ok(json) if is_json => serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?,
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    };
+    output.push('\n');
+    output.push_str(&body);
+
+    Ok(output)
+}
+
+/// runs endpoint flow with guards without starting the server
+pub fn run_endpoint(
+    args: &Args,
+    method: &str,
+    route: &str,
+    input: Option<&str>,
+) -> Result<String, String> {
+    let collection = EndpointsCollection::parse_from_dir(&args.dsl_path);
+    let (endpoint, request) =
+        prepare_request(&collection, method, route, RequestInput::load(input)?)?;
+    let engine = Engine::from_endpoint(endpoint, &EngineConfig::from_args(args));
+
+    let (parts, bytes) = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?
+        .block_on(execute(engine, request))?;
+
+    format_response(&parts, &bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use crate::args::types::Args;
+    use crate::commands::run::run_endpoint;
+
+    #[test]
+    fn test_run_endpoint() {
+        let args = Args::parse_from(["rstrouter", "--dsl-path", "./unittest_dsl"]);
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.json");
+        std::fs::write(&input, r#"{"params": {"some": "value"}}"#).unwrap();
+
+        let output = run_endpoint(&args, "get", "/test/test/endp", input.to_str()).unwrap();
+        assert!(output.starts_with("200 OK\n"), "{}", output);
+        assert!(
+            output.ends_with("\n{\n  \"response\": \"ok\"\n}"),
+            "{}",
+            output
+        );
+
+        assert!(run_endpoint(&args, "GET", "/not/exists", input.to_str()).is_err());
+
+        std::fs::write(&input, r#"{"query": {}}"#).unwrap();
+        assert!(run_endpoint(&args, "POST", "/test/endp", input.to_str()).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::http::response::Parts;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use serde_json_path::JsonPath;
+
+use crate::args::types::Args;
+use crate::commands::run::{RequestInput, execute, prepare_request};
+use crate::endpoints::parser::{EndpointsCollection, is_test_file};
+use crate::engine::mocks::{UpstreamMock, UpstreamMocks, with_mocks};
+use crate::engine::{Engine, EngineConfig};
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// `*.test.yml` file, suite mocks are used by every case after its own mocks
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suite {
+    #[serde(default)]
+    mocks: Vec<UpstreamMock>,
+    cases: Vec<Case>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    name: String,
+    request: CaseRequest,
+    #[serde(default)]
+    mocks: Vec<UpstreamMock>,
+    #[serde(default)]
+    expect: Expectation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CaseRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    route: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    params: HashMap<String, String>,
+    #[serde(default)]
+    body: JsonValue,
+}
+
+/// every set matcher has to pass, response body is json or a string
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Expectation {
+    status: Option<u16>,
+    headers: HashMap<String, String>,
+    /// exact body
+    body: Option<JsonValue>,
+    /// body has to contain these fields and array items
+    contains: Option<JsonValue>,
+    /// JSONPath query to expected value, queries matching several nodes give an array
+    jsonpath: BTreeMap<String, JsonValue>,
+}
+
+fn is_subset(expected: &JsonValue, actual: &JsonValue) -> bool {
+    match (expected, actual) {
+        (JsonValue::Object(e), JsonValue::Object(a)) => e
+            .iter()
+            .all(|(k, v)| a.get(k).is_some_and(|av| is_subset(v, av))),
+        (JsonValue::Array(e), JsonValue::Array(a)) => {
+            e.iter().all(|ev| a.iter().any(|av| is_subset(ev, av)))
+        }
+        (e, a) => e == a,
+    }
+}
+
+fn query_path(path: &str, body: &JsonValue) -> Result<JsonValue, String> {
+    let path = JsonPath::parse(path).map_err(|e| format!("bad JSONPath {}: {}", path, e))?;
+    let nodes = path.query(body).all();
+
+    match nodes.as_slice() {
+        [] => Err(format!("{} matches nothing", path)),
+        [node] => Ok((*node).clone()),
+        nodes => Ok(JsonValue::Array(
+            nodes.iter().map(|n| (*n).clone()).collect(),
+        )),
+    }
+}
+
+impl Expectation {
+    /// failure messages, empty when response is as expected
+    fn check(&self, parts: &Parts, body: &JsonValue) -> Vec<String> {
+        let mut failures = vec![];
+
+        if let Some(status) = self.status
+            && parts.status.as_u16() != status
+        {
+            failures.push(format!("status {} != {}", parts.status.as_u16(), status));
+        }
+
+        for (name, expected) in &self.headers {
+            let actual = parts.headers.get(name).and_then(|v| v.to_str().ok());
+            if actual != Some(expected.as_str()) {
+                failures.push(format!("header {}: {:?} != {:?}", name, actual, expected));
+            }
+        }
+
+        if let Some(expected) = &self.body
+            && expected != body
+        {
+            failures.push(format!("body {} != {}", body, expected));
+        }
+
+        if let Some(expected) = &self.contains
+            && !is_subset(expected, body)
+        {
+            failures.push(format!("body {} does not contain {}", body, expected));
+        }
+
+        for (path, expected) in &self.jsonpath {
+            match query_path(path, body) {
+                Ok(actual) if &actual == expected => {}
+                Ok(actual) => failures.push(format!("{}: {} != {}", path, actual, expected)),
+                Err(e) => failures.push(e),
+            }
+        }
+
+        failures
+    }
+}
+
+fn body_value(bytes: &Bytes) -> JsonValue {
+    serde_json::from_slice(bytes)
+        .unwrap_or_else(|_| JsonValue::String(String::from_utf8_lossy(bytes).to_string()))
+}
+
+pub struct CaseResult {
+    pub name: String,
+    pub failures: Vec<String>,
+}
+
+async fn run_case(
+    collection: &EndpointsCollection,
+    config: &EngineConfig,
+    suite_mocks: &[UpstreamMock],
+    case: Case,
+) -> CaseResult {
+    let request = case.request;
+    let input = RequestInput {
+        headers: request.headers,
+        params: request.params,
+        body: request.body,
+    };
+    let (endpoint, prepared) =
+        match prepare_request(collection, &request.method, &request.route, input) {
+            Ok(r) => r,
+            Err(e) => {
+                return CaseResult {
+                    name: case.name,
+                    failures: vec![e],
+                };
+            }
+        };
+
+    let mocks = case.mocks.into_iter().chain(suite_mocks.iter().cloned());
+    let mocks = Arc::new(UpstreamMocks::new(mocks.collect()));
+    let engine = Engine::from_endpoint(endpoint, config);
+
+    let mut failures = match with_mocks(Some(mocks.clone()), execute(engine, prepared)).await {
+        Ok((parts, bytes)) => case.expect.check(&parts, &body_value(&bytes)),
+        Err(e) => vec![e],
+    };
+    failures.extend(
+        mocks
+            .unmatched()
+            .into_iter()
+            .map(|call| format!("no mock for upstream call {}", call)),
+    );
+
+    CaseResult {
+        name: case.name,
+        failures,
+    }
+}
+
+/// suite files under the path, sorted to keep report stable
+fn find_suites(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+
+    let mut suites: Vec<PathBuf> = read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .flat_map(|p| {
+            if p.is_dir() {
+                find_suites(&p)
+            } else if is_test_file(&p) {
+                vec![p]
+            } else {
+                vec![]
+            }
+        })
+        .collect();
+    suites.sort();
+    suites
+}
+
+async fn run_suite(
+    collection: &EndpointsCollection,
+    config: &EngineConfig,
+    path: &Path,
+) -> Result<Vec<CaseResult>, String> {
+    let content = read_to_string(path).map_err(|e| e.to_string())?;
+    let suite: Suite = serde_yaml_ng::from_str(&content).map_err(|e| e.to_string())?;
+
+    let mut results = vec![];
+    for case in suite.cases {
+        results.push(run_case(collection, config, &suite.mocks, case).await);
+    }
+    Ok(results)
+}
+
+/// runs every suite under `path`, failed cases are reported with reasons
+pub fn run_suites(args: &Args, path: &str) -> Result<(), String> {
+    let collection = EndpointsCollection::parse_from_dir(&args.dsl_path);
+    let config = EngineConfig::from_args(args);
+    let suites = find_suites(Path::new(path));
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let (mut passed, mut failed) = (0, 0);
+    for suite in suites {
+        println!("{}", suite.display());
+        let results = match runtime.block_on(run_suite(&collection, &config, &suite)) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("  FAIL cannot load suite: {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        for result in results {
+            if result.failures.is_empty() {
+                println!("  ok   {}", result.name);
+                passed += 1;
+                continue;
+            }
+
+            println!("  FAIL {}", result.name);
+            result
+                .failures
+                .iter()
+                .for_each(|f| println!("       {}", f));
+            failed += 1;
+        }
+    }
+
+    println!("{} passed, {} failed", passed, failed);
+    match failed {
+        0 => Ok(()),
+        n => Err(format!("{} test cases failed", n)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+    use httpmock::MockServer;
+    use serde_json::json;
+
+    use crate::args::types::Args;
+    use crate::commands::suite::{is_subset, query_path, run_suites};
+    use crate::endpoints::parser::EndpointsCollection;
+
+    #[test]
+    fn test_matchers() {
+        let body = json!({"response": {"users": [{"id": 1, "name": "a"}, {"id": 2}], "total": 2}});
+
+        assert!(is_subset(&json!({"response": {"total": 2}}), &body));
+        assert!(is_subset(
+            &json!({"response": {"users": [{"id": 2}]}}),
+            &body
+        ));
+        assert!(!is_subset(
+            &json!({"response": {"users": [{"id": 3}]}}),
+            &body
+        ));
+        assert!(!is_subset(&json!({"response": {"total": "2"}}), &body));
+
+        assert_eq!(query_path("$.response.total", &body), Ok(json!(2)));
+        assert_eq!(
+            query_path("$.response.users[*].id", &body),
+            Ok(json!([1, 2]))
+        );
+        assert!(query_path("$.response.missing", &body).is_err());
+        assert!(query_path("response", &body).is_err());
+    }
+
+    #[test]
+    fn test_run_suites() {
+        // mocked upstream must not be called
+        let upstream = MockServer::start();
+        let real = upstream.mock(|_, then| {
+            then.status(500);
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("GET/users")).unwrap();
+        std::fs::write(
+            dir.join("GET/users/[id].yml"),
+            format!(
+                r#"
+                    fetch:
+                      call: http.get
+                      args:
+                        url: ${{"{}/users/" + incoming.path.id}}
+                      result: user
+
+                    respond:
+                      return: ${{user.response.body}}
+                "#,
+                upstream.base_url()
+            ),
+        )
+        .unwrap();
+
+        let suite = dir.join("GET/users/[id].test.yml");
+        std::fs::write(
+            &suite,
+            format!(
+                r#"
+                    mocks:
+                      - url: {url}/users/1
+                        body: {{id: 1, name: John, roles: [admin, dev]}}
+                    cases:
+                      - name: returns user
+                        request:
+                          route: /users/1
+                        expect:
+                          status: 200
+                          headers:
+                            content-type: application/json
+                          contains:
+                            response: {{name: John}}
+                          jsonpath:
+                            $.response.roles[0]: admin
+                      - name: case mocks win
+                        request:
+                          route: /users/1
+                        mocks:
+                          - method: GET
+                            url: {url}/users/1
+                            body: {{id: 1, name: Jane}}
+                        expect:
+                          body:
+                            response: {{id: 1, name: Jane}}
+                "#,
+                url = upstream.base_url()
+            ),
+        )
+        .unwrap();
+
+        let collection = EndpointsCollection::parse_from_dir(dir.to_str().unwrap());
+        assert_eq!(collection.endpoints.len(), 1);
+
+        let args = Args::parse_from(["rstrouter", "--dsl-path", dir.to_str().unwrap()]);
+        assert_eq!(run_suites(&args, dir.to_str().unwrap()), Ok(()));
+        real.assert_hits(0);
+
+        std::fs::write(
+            &suite,
+            r#"
+                cases:
+                  - name: upstream is not mocked
+                    request:
+                      route: /users/1
+                    expect:
+                      status: 404
+            "#,
+        )
+        .unwrap();
+        assert!(run_suites(&args, suite.to_str().unwrap()).is_err());
+        real.assert_hits(0);
+    }
+}
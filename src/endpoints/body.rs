@@ -0,0 +1,379 @@
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequest, Multipart, Request as AxumRequest};
+use axum::http::{HeaderMap, header};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use log::warn;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event as XmlEvent};
+use quick_xml::reader::Reader as XmlReader;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// raw payload is exposed as utf-8 string, binary payload as base64 string
+pub fn raw_body_to_json(bytes: &Bytes) -> JsonValue {
+    if bytes.is_empty() {
+        return JsonValue::Null;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(s) => JsonValue::String(s.to_string()),
+        Err(_) => JsonValue::String(BASE64.encode(bytes)),
+    }
+}
+
+fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_json(content_type: &str) -> bool {
+    content_type == "application/json" || content_type.ends_with("+json")
+}
+
+fn is_xml(content_type: &str) -> bool {
+    content_type == "application/xml"
+        || content_type == "text/xml"
+        || content_type.ends_with("+xml")
+}
+
+pub async fn parse_body(headers: &HeaderMap, bytes: &Bytes) -> JsonValue {
+    if bytes.is_empty() {
+        return JsonValue::Null;
+    }
+
+    let content_type = content_type(headers);
+
+    if is_json(&content_type) {
+        return serde_json::from_slice(bytes).unwrap_or(JsonValue::Null);
+    }
+
+    if content_type == "application/x-www-form-urlencoded" {
+        return parse_form(bytes);
+    }
+
+    if content_type == "multipart/form-data" {
+        return parse_multipart(headers, bytes).await;
+    }
+
+    if is_xml(&content_type) {
+        return parse_xml(bytes);
+    }
+
+    if content_type.starts_with("text/") {
+        return JsonValue::String(String::from_utf8_lossy(bytes).to_string());
+    }
+
+    JsonValue::Null
+}
+
+fn insert_repeated(map: &mut JsonMap<String, JsonValue>, key: String, value: JsonValue) {
+    // repeated keys are collected into array
+    match map.get_mut(&key) {
+        Some(JsonValue::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = JsonValue::Array(vec![first, value]);
+        }
+        None => {
+            map.insert(key, value);
+        }
+    }
+}
+
+fn parse_form(bytes: &Bytes) -> JsonValue {
+    let Ok(pairs) = serde_urlencoded::from_bytes::<Vec<(String, String)>>(bytes) else {
+        warn!("cannot parse urlencoded form body");
+        return JsonValue::Null;
+    };
+
+    let mut map = JsonMap::new();
+    for (k, v) in pairs {
+        insert_repeated(&mut map, k, JsonValue::String(v));
+    }
+
+    JsonValue::Object(map)
+}
+
+async fn parse_multipart(headers: &HeaderMap, bytes: &Bytes) -> JsonValue {
+    let mut request = AxumRequest::new(Body::from(bytes.clone()));
+    *request.headers_mut() = headers.clone();
+
+    let Ok(mut multipart) = Multipart::from_request(request, &()).await else {
+        warn!("cannot parse multipart body");
+        return JsonValue::Null;
+    };
+
+    let mut map = JsonMap::new();
+    while let Ok(Some(field)) = multipart.next_field().await {
+        let name = field.name().unwrap_or_default().to_string();
+        let file_name = field.file_name().map(|f| f.to_string());
+        let field_content_type = field.content_type().map(|c| c.to_string());
+
+        let Ok(content) = field.bytes().await else {
+            warn!("cannot read multipart field {}", name);
+            break;
+        };
+
+        let value = match file_name {
+            Some(file_name) => serde_json::json!({
+                "filename": file_name,
+                "contentType": field_content_type,
+                "size": content.len(),
+                "content": BASE64.encode(&content),
+            }),
+            None => JsonValue::String(String::from_utf8_lossy(&content).to_string()),
+        };
+
+        insert_repeated(&mut map, name, value);
+    }
+
+    JsonValue::Object(map)
+}
+
+struct XmlFrame {
+    name: String,
+    fields: JsonMap<String, JsonValue>,
+    text: String,
+}
+
+impl XmlFrame {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            fields: JsonMap::new(),
+            text: String::new(),
+        }
+    }
+
+    fn from_start(e: &BytesStart) -> Self {
+        let mut frame = Self::new(String::from_utf8_lossy(e.name().as_ref()).into());
+        for attr in e.attributes().flatten() {
+            let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
+            let value = attr
+                .unescape_value()
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            frame.fields.insert(key, JsonValue::String(value));
+        }
+        frame
+    }
+
+    fn close_into(self, stack: &mut [XmlFrame]) {
+        let (name, value) = self.into_value();
+        if let Some(parent) = stack.last_mut() {
+            insert_repeated(&mut parent.fields, name, value);
+        }
+    }
+
+    fn into_value(self) -> (String, JsonValue) {
+        let text = self.text.trim();
+        if self.fields.is_empty() {
+            return (self.name, JsonValue::String(text.to_string()));
+        }
+
+        let mut fields = self.fields;
+        if !text.is_empty() {
+            fields.insert("#text".into(), JsonValue::String(text.to_string()));
+        }
+        (self.name, JsonValue::Object(fields))
+    }
+}
+
+/// xml is converted to json: attributes are prefixed with `@`,
+/// mixed text is stored under `#text`, repeated elements become arrays
+fn parse_xml(bytes: &Bytes) -> JsonValue {
+    let Ok(source) = std::str::from_utf8(bytes) else {
+        warn!("xml body is not utf-8");
+        return JsonValue::Null;
+    };
+
+    let mut reader = XmlReader::from_str(source);
+    let mut stack = vec![XmlFrame::new("".into())];
+
+    loop {
+        let event = match reader.read_event() {
+            Ok(e) => e,
+            Err(e) => {
+                warn!("cannot parse xml body: {}", e);
+                return JsonValue::Null;
+            }
+        };
+
+        match event {
+            XmlEvent::Start(e) => stack.push(XmlFrame::from_start(&e)),
+            XmlEvent::Empty(e) => XmlFrame::from_start(&e).close_into(&mut stack),
+            XmlEvent::End(_) => {
+                if let Some(frame) = stack.pop() {
+                    frame.close_into(&mut stack);
+                }
+            }
+            XmlEvent::Text(t) => {
+                if let Some(frame) = stack.last_mut() {
+                    frame.text.push_str(&t.decode().unwrap_or_default());
+                }
+            }
+            XmlEvent::CData(t) => {
+                if let Some(frame) = stack.last_mut() {
+                    frame.text.push_str(&t.decode().unwrap_or_default());
+                }
+            }
+            XmlEvent::GeneralRef(r) => {
+                let resolved = match r.resolve_char_ref() {
+                    Ok(Some(c)) => Some(c.to_string()),
+                    _ => r
+                        .decode()
+                        .ok()
+                        .and_then(|name| resolve_predefined_entity(&name))
+                        .map(|s| s.to_string()),
+                };
+                if let (Some(frame), Some(resolved)) = (stack.last_mut(), resolved) {
+                    frame.text.push_str(&resolved);
+                }
+            }
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+    }
+
+    let Some(root) = stack.pop() else {
+        return JsonValue::Null;
+    };
+
+    if !stack.is_empty() || root.fields.is_empty() {
+        warn!("xml body has unclosed or no root element");
+        return JsonValue::Null;
+    }
+
+    JsonValue::Object(root.fields)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::endpoints::body::{parse_body, raw_body_to_json};
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, HeaderValue, header};
+    use serde_json::json;
+
+    fn headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_str(content_type).unwrap(),
+        );
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_parse_json() {
+        let res = parse_body(
+            &headers("application/json; charset=utf-8"),
+            &Bytes::from(r#"{"a": [1, 2]}"#),
+        )
+        .await;
+        assert_eq!(res, json!({"a": [1, 2]}));
+
+        let res = parse_body(&headers("application/json"), &Bytes::from("not json")).await;
+        assert!(res.is_null());
+    }
+
+    #[tokio::test]
+    async fn test_parse_form() {
+        let res = parse_body(
+            &headers("application/x-www-form-urlencoded"),
+            &Bytes::from("a=b&c=d+e&a=f"),
+        )
+        .await;
+        assert_eq!(res, json!({"a": ["b", "f"], "c": "d e"}));
+    }
+
+    #[tokio::test]
+    async fn test_parse_multipart() {
+        let body = concat!(
+            "--XBOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"field\"\r\n",
+            "\r\n",
+            "value\r\n",
+            "--XBOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "hello\r\n",
+            "--XBOUNDARY--\r\n",
+        );
+        let res = parse_body(
+            &headers("multipart/form-data; boundary=XBOUNDARY"),
+            &Bytes::from(body),
+        )
+        .await;
+
+        assert_eq!(
+            res,
+            json!({
+                "field": "value",
+                "file": {
+                    "filename": "a.txt",
+                    "contentType": "text/plain",
+                    "size": 5,
+                    "content": "aGVsbG8=",
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_text() {
+        let res = parse_body(&headers("text/plain"), &Bytes::from("hello world")).await;
+        assert_eq!(res, "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_parse_xml() {
+        let res = parse_body(
+            &headers("application/xml"),
+            &Bytes::from(
+                r#"<?xml version="1.0"?>
+                <order id="12">
+                  <item>a &amp; b</item>
+                  <item>c</item>
+                  <note lang="en">fast<![CDATA[ <please> ]]></note>
+                  <empty/>
+                </order>"#,
+            ),
+        )
+        .await;
+
+        assert_eq!(
+            res,
+            json!({
+                "order": {
+                    "@id": "12",
+                    "item": ["a & b", "c"],
+                    "note": {"@lang": "en", "#text": "fast <please>"},
+                    "empty": "",
+                }
+            })
+        );
+
+        let res = parse_body(&headers("text/xml"), &Bytes::from("<a><b></a>")).await;
+        assert!(res.is_null());
+    }
+
+    #[tokio::test]
+    async fn test_parse_unknown() {
+        let res = parse_body(&headers("application/octet-stream"), &Bytes::from("a")).await;
+        assert!(res.is_null());
+
+        let res = parse_body(&HeaderMap::new(), &Bytes::from("a")).await;
+        assert!(res.is_null());
+    }
+
+    #[test]
+    fn test_raw_body_to_json() {
+        assert!(raw_body_to_json(&Bytes::new()).is_null());
+        assert_eq!(raw_body_to_json(&Bytes::from("a=b")), "a=b");
+        assert_eq!(raw_body_to_json(&Bytes::from_static(&[0xff, 0xfe])), "//4=");
+    }
+}
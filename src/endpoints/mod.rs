@@ -1,38 +1,107 @@
+use std::sync::Arc;
+
 use axum::Router;
+use axum::extract::Request;
 use itertools::Itertools;
 use log::info;
-use rstmytype::build_open_api;
+use serde::Serialize;
+use utoipa::openapi::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+use crate::access_log::access_log;
+use crate::endpoints::openapi::{build_spec, load_metadata_or_default};
 use crate::endpoints::parser::EndpointsCollection;
 use crate::endpoints::route::get_route;
+use crate::endpoints::statics::{StaticDir, find_static_dirs, serve_static};
+use crate::engine::EngineConfig;
+use crate::metrics::track_requests;
 
+mod body;
+pub mod openapi;
 pub mod parser;
+pub mod reload;
 mod route;
+pub mod scaffold;
+mod statics;
 pub mod types;
 
-pub fn load_swagger(app: Router, collection: &EndpointsCollection) -> Router {
-    app.merge(SwaggerUi::new("/docs").url("/docs/openapi.json", build_open_api(collection)))
+pub fn load_swagger(app: Router, spec: OpenApi) -> Router {
+    app.merge(SwaggerUi::new("/docs").url("/docs/openapi.json", spec))
+}
+
+/// everything parsed from DSL dir, router is built from it
+pub struct DslTree {
+    pub collection: EndpointsCollection,
+    pub static_dirs: Vec<StaticDir>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteInfo {
+    pub method: String,
+    pub url: String,
+    pub tag: String,
+    pub guards: Vec<String>,
+    pub source: String,
 }
 
-pub fn load_dsl_endpoints(args: &crate::args::types::Args, app: Router) -> Router {
-    let collection = EndpointsCollection::parse_from_dir(&args.dsl_path);
+impl DslTree {
+    pub fn parse(dsl_path: &str) -> Self {
+        Self {
+            collection: EndpointsCollection::parse_from_dir(dsl_path),
+            static_dirs: find_static_dirs(dsl_path),
+        }
+    }
+
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        let endpoints = self.collection.endpoints.iter().map(|e| RouteInfo {
+            method: e.method_name(),
+            url: e.url_path.clone(),
+            tag: e.tag.clone(),
+            guards: e.guards.iter().map(|g| g.source.clone()).collect(),
+            source: e.source.clone(),
+        });
+        let static_dirs = self.static_dirs.iter().map(|d| RouteInfo {
+            method: "STATIC".to_string(),
+            url: format!("{}/", d.url_prefix),
+            tag: "".to_string(),
+            guards: vec![],
+            source: d.dir.display().to_string(),
+        });
+
+        endpoints
+            .chain(static_dirs)
+            .sorted_by(|l, r| (&l.url, &l.method).cmp(&(&r.url, &r.method)))
+            .collect()
+    }
+}
+
+pub fn build_router(args: &crate::args::types::Args, tree: &DslTree, app: Router) -> Router {
+    let collection = &tree.collection;
+    let config = EngineConfig::from_args(args);
 
     info!("Loaded next endpoints collection: {}", collection);
 
     let mut app = collection
         .endpoints
         .iter()
+        .sorted_by(|l, r| l.url_path.cmp(&r.url_path))
         .chunk_by(|e| &e.url_path)
         .into_iter()
         .fold(app, |app, (key, chunk_iter)| {
-            app.route(key, get_route(chunk_iter.collect(), &args.dsl_path))
+            app.route(key, get_route(chunk_iter.collect(), &config))
         });
 
+    if !tree.static_dirs.is_empty() {
+        info!("Serving static dirs: {:?}", tree.static_dirs);
+        let static_dirs = Arc::new(tree.static_dirs.clone());
+        app = app.fallback(|q: Request| serve_static(static_dirs, q));
+    }
+
     if !args.disable_swagger {
         info!("Loading swagger and building types");
-        app = load_swagger(app, &collection);
+        app = load_swagger(app, build_spec(collection, load_metadata_or_default(args)));
     }
 
-    app
+    app.layer(axum::middleware::from_fn(track_requests))
+        .layer(axum::middleware::from_fn(access_log))
 }
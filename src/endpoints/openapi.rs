@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use log::warn;
+use rstmytype::build_open_api;
+use serde::Deserialize;
+use utoipa::openapi::{
+    Components, Contact, OpenApi, Server,
+    security::{SecurityRequirement, SecurityScheme},
+};
+
+use crate::args::types::Args;
+use crate::endpoints::parser::EndpointsCollection;
+
+/// project level metadata file in the root of DSL dir
+const METADATA_FILES: [&str; 2] = [".openapi.yml", ".openapi.yaml"];
+
+/// project level part of OpenAPI document, endpoints are described by `declare` tasks
+#[derive(Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ApiMetadata {
+    pub title: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub contact: Option<Contact>,
+    pub servers: Option<Vec<Server>>,
+    pub security_schemes: Option<BTreeMap<String, SecurityScheme>>,
+    pub security: Option<Vec<SecurityRequirement>>,
+}
+
+impl ApiMetadata {
+    fn from_file(path: &PathBuf) -> Result<Self, String> {
+        let content = read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_yaml_ng::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// `--openapi-meta` file or root file of DSL dir, CLI values win over file values
+    pub fn load(args: &Args) -> Result<Self, String> {
+        let file = match &args.openapi_meta {
+            Some(path) => Some(PathBuf::from(path)),
+            None => METADATA_FILES
+                .iter()
+                .map(|f| PathBuf::from(&args.dsl_path).join(f))
+                .find(|p| p.is_file()),
+        };
+
+        let mut metadata = match file {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        if args.api_title.is_some() {
+            metadata.title = args.api_title.clone();
+        }
+        if args.api_version.is_some() {
+            metadata.version = args.api_version.clone();
+        }
+        if args.api_description.is_some() {
+            metadata.description = args.api_description.clone();
+        }
+        if !args.api_server.is_empty() {
+            metadata.servers = Some(args.api_server.iter().map(Server::new).collect());
+        }
+
+        Ok(metadata)
+    }
+
+    fn apply(self, api: &mut OpenApi) {
+        if let Some(title) = self.title {
+            api.info.title = title;
+        }
+        if let Some(version) = self.version {
+            api.info.version = version;
+        }
+        if self.description.is_some() {
+            api.info.description = self.description;
+        }
+        if self.contact.is_some() {
+            api.info.contact = self.contact;
+        }
+        if self.servers.is_some() {
+            api.servers = self.servers;
+        }
+        if let Some(schemes) = self.security_schemes {
+            api.components
+                .get_or_insert_with(Components::new)
+                .security_schemes
+                .extend(schemes);
+        }
+        if self.security.is_some() {
+            api.security = self.security;
+        }
+    }
+}
+
+pub fn build_spec(collection: &EndpointsCollection, metadata: ApiMetadata) -> OpenApi {
+    let mut api = build_open_api(collection);
+    metadata.apply(&mut api);
+    api
+}
+
+/// bad metadata is not fatal for the server, document is built without it
+pub fn load_metadata_or_default(args: &Args) -> ApiMetadata {
+    ApiMetadata::load(args)
+        .map_err(|e| warn!("cannot load OpenAPI metadata: {}", e))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use crate::args::types::Args;
+    use crate::endpoints::openapi::{ApiMetadata, build_spec};
+    use crate::endpoints::parser::EndpointsCollection;
+
+    #[test]
+    fn test_metadata_from_file() {
+        let metadata: ApiMetadata = serde_yaml_ng::from_str(
+            r#"
+                title: Users API
+                version: 2.1.0
+                contact:
+                  name: Platform team
+                  email: platform@example.com
+                servers:
+                  - url: https://api.example.com
+                    description: production
+                securitySchemes:
+                  bearer:
+                    type: http
+                    scheme: bearer
+                security:
+                  - bearer: []
+            "#,
+        )
+        .unwrap();
+
+        let api = build_spec(&EndpointsCollection { endpoints: vec![] }, metadata);
+
+        let json = serde_json::to_value(&api).unwrap();
+        assert_eq!(json["info"]["title"], "Users API");
+        assert_eq!(json["info"]["version"], "2.1.0");
+        assert_eq!(json["info"]["contact"]["email"], "platform@example.com");
+        assert_eq!(json["servers"][0]["url"], "https://api.example.com");
+        assert_eq!(
+            json["components"]["securitySchemes"]["bearer"]["scheme"],
+            "bearer"
+        );
+        assert_eq!(json["security"][0]["bearer"], serde_json::json!([]));
+
+        assert!(serde_yaml_ng::from_str::<ApiMetadata>("titel: typo").is_err());
+    }
+
+    #[test]
+    fn test_cli_overrides() {
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            "./unittest_dsl",
+            "--api-title",
+            "From CLI",
+            "--api-server",
+            "http://a",
+            "--api-server",
+            "http://b",
+        ]);
+
+        let metadata = ApiMetadata::load(&args).unwrap();
+        let api = build_spec(&EndpointsCollection { endpoints: vec![] }, metadata);
+        assert_eq!(api.info.title, "From CLI");
+        assert_eq!(api.servers.unwrap().len(), 2);
+
+        let args = Args::parse_from(["rstrouter", "--dsl-path", "./unittest_dsl"]);
+        let metadata = ApiMetadata::load(&args).unwrap();
+        let api = build_spec(&EndpointsCollection { endpoints: vec![] }, metadata);
+        assert_eq!(api.info.title, "rstrouter");
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("meta.yml");
+        std::fs::write(&file, "title: From file\nversion: 1.0.0\n").unwrap();
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            "./unittest_dsl",
+            "--openapi-meta",
+            file.to_str().unwrap(),
+            "--api-version",
+            "1.1.0",
+        ]);
+        let metadata = ApiMetadata::load(&args).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("From file"));
+        assert_eq!(metadata.version.as_deref(), Some("1.1.0"));
+
+        std::fs::write(&file, "titel: typo\n").unwrap();
+        assert!(ApiMetadata::load(&args).is_err());
+    }
+}
@@ -2,12 +2,14 @@ use itertools::Itertools;
 use log::warn;
 use rstmytype::{ApiEndpoint, ApiEndpointMethod, ApiProject};
 use serde_yaml_ng::{Mapping as YmlMapping, Value as YmlValue};
+use std::collections::{HashMap, HashSet};
 use std::fs::{read_dir, read_to_string};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug)]
 pub struct Guard {
     pub yml_content: YmlValue,
+    pub source: String,
 }
 
 #[derive(Debug, Clone)]
@@ -18,6 +20,10 @@ pub struct Endpoint {
     pub method: ApiEndpointMethod,
     pub yml_content: YmlValue,
     pub merged_declaration: String,
+    /// endpoint from `WS/` dir, flow is executed for every websocket message
+    pub websocket: bool,
+    /// file the endpoint is parsed from
+    pub source: String,
 }
 
 #[derive(Debug)]
@@ -49,10 +55,137 @@ impl ApiProject for EndpointsCollection {
     }
 
     fn get_endpoints_iter<'a>(&'a self) -> impl Iterator<Item = &'a impl ApiEndpoint> {
-        self.endpoints.iter()
+        // websocket endpoints can not be described by openapi
+        self.endpoints.iter().filter(|e| !e.websocket)
     }
 }
 
+/// `[id]` and `{id}` file or dir names become `{id}` axum path captures,
+/// `[...rest]` becomes `{*rest}` catch-all capture
+fn url_segment(name: &str) -> String {
+    if let Some(param) = name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        if let Some(rest) = param.strip_prefix("...") {
+            return format!("{{*{}}}", rest);
+        }
+        return format!("{{{}}}", param);
+    }
+
+    name.to_string()
+}
+
+pub fn path_params(url_path: &str) -> Vec<&str> {
+    url_path
+        .split('/')
+        .flat_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|param| param.trim_start_matches('*'))
+        .collect()
+}
+
+fn capture_name(segment: &str) -> Option<&str> {
+    segment.strip_prefix('{')?.strip_suffix('}')
+}
+
+/// url without capture names, e.g. `/users/{}/files/{*}`
+pub fn route_shape(url_path: &str) -> String {
+    url_path
+        .split('/')
+        .map(|segment| match capture_name(segment) {
+            Some(name) if name.starts_with('*') => "{*}",
+            Some(_) => "{}",
+            None => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// captures at the same position named differently, axum cannot register both routes,
+/// e.g. `/users/{id}` and `/users/{userId}/posts`
+pub fn conflicting_captures<'a, 'b>(
+    url_path: &'a str,
+    other: &'b str,
+) -> Option<(&'a str, &'b str)> {
+    for (segment, other_segment) in url_path.split('/').zip(other.split('/')) {
+        match (capture_name(segment), capture_name(other_segment)) {
+            (Some(name), Some(other_name))
+                if name.starts_with('*') == other_name.starts_with('*') =>
+            {
+                if name != other_name {
+                    return Some((name, other_name));
+                }
+            }
+            _ if segment == other_segment => {}
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+fn is_catch_all_last(url_path: &str) -> bool {
+    // axum allows catch-all capture only at the end of the route
+    !url_path
+        .split('/')
+        .rev()
+        .skip(1)
+        .any(|segment| segment.starts_with("{*"))
+}
+
+/// `*.test.yml` suites live next to endpoints but are not endpoints
+pub fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|f| f.to_str())
+        .is_some_and(|f| f.ends_with(".test.yml") || f.ends_with(".test.yaml"))
+}
+
+/// concrete request path matched against endpoint url
+pub struct RouteMatch {
+    pub raw_path: String,
+    pub params: HashMap<String, String>,
+    static_segments: usize,
+}
+
+/// route is either a concrete path, e.g. `/users/42`, or the endpoint url `/users/{id}`
+fn match_route(url_path: &str, route: &str) -> Option<RouteMatch> {
+    if url_path == route {
+        return Some(RouteMatch {
+            raw_path: route.to_string(),
+            params: HashMap::new(),
+            static_segments: usize::MAX,
+        });
+    }
+
+    let mut params = HashMap::new();
+    let mut static_segments = 0;
+    let mut route_segments = route.trim_start_matches('/').split('/');
+    for segment in url_path.trim_start_matches('/').split('/') {
+        if let Some(name) = segment.strip_prefix("{*").and_then(|s| s.strip_suffix('}')) {
+            let rest = route_segments.by_ref().collect::<Vec<_>>().join("/");
+            params.insert(name.to_string(), rest);
+            break;
+        }
+
+        let value = route_segments.next()?;
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) if !value.is_empty() => {
+                params.insert(name.to_string(), value.to_string());
+            }
+            Some(_) => return None,
+            None if segment == value => static_segments += 1,
+            None => return None,
+        }
+    }
+
+    if route_segments.next().is_some() {
+        return None;
+    }
+
+    Some(RouteMatch {
+        raw_path: route.to_string(),
+        params,
+        static_segments,
+    })
+}
+
 struct SoftList<'a, T> {
     el: T,
     next: Option<&'a Self>,
@@ -86,7 +219,22 @@ impl<'a, T> Iterator for SoftListIter<'a, T> {
 }
 
 impl EndpointsCollection {
+    /// static segments win over captures like in the router, websocket endpoints are skipped
+    pub fn find(&self, method: &str, route: &str) -> Option<(&Endpoint, RouteMatch)> {
+        self.endpoints
+            .iter()
+            .filter(|e| !e.websocket && e.method_name() == method)
+            .flat_map(|e| Some((e, match_route(&e.url_path, route)?)))
+            .max_by_key(|(_, m)| m.static_segments)
+    }
+
+    /// endpoints conflicting with others are skipped, axum panics on them
     pub fn parse_from_dir(dsl_dir: &str) -> Self {
+        Self::parse_all_from_dir(dsl_dir).without_conflicts()
+    }
+
+    /// every endpoint of DSL dir, including the ones which cannot be routed together
+    pub fn parse_all_from_dir(dsl_dir: &str) -> Self {
         let current_dir = PathBuf::from(dsl_dir);
         let current_url = "";
         let mut endpoints_acc = Vec::new();
@@ -96,6 +244,31 @@ impl EndpointsCollection {
         }
     }
 
+    /// endpoints are checked in order of their files, so the same one is kept on every start
+    fn without_conflicts(self) -> Self {
+        let mut kept: Vec<&Endpoint> = vec![];
+        for endpoint in self
+            .endpoints
+            .iter()
+            .sorted_by(|l, r| l.source.cmp(&r.source))
+        {
+            match kept.iter().find_map(|k| endpoint.conflict_with(k)) {
+                Some(conflict) => warn!("Skipping endpoint {}: {}", endpoint.source, conflict),
+                None => kept.push(endpoint),
+            }
+        }
+
+        let kept: HashSet<&str> = kept.iter().map(|e| e.source.as_str()).collect();
+        Self {
+            endpoints: self
+                .endpoints
+                .iter()
+                .filter(|e| kept.contains(e.source.as_str()))
+                .cloned()
+                .collect(),
+        }
+    }
+
     fn parse_from_dir_rec(
         current_url: &str,
         current_dir: &PathBuf,
@@ -126,9 +299,9 @@ impl EndpointsCollection {
                     return;
                 }
 
-                if f.file_name() == "POST" {
+                if let Some(method) = f.file_name().to_str().and_then(Self::parse_method_dir) {
                     Endpoint::parse_from_dir_rec(
-                        &ApiEndpointMethod::Post,
+                        &method,
                         current_url,
                         current_url,
                         &f_path,
@@ -138,7 +311,14 @@ impl EndpointsCollection {
                     return;
                 }
 
-                if f.file_name() == "GET" {
+                if f.file_name() == "STATIC" {
+                    // served as plain files, see endpoints::statics
+                    return;
+                }
+
+                if f.file_name() == "WS" {
+                    // websocket handshake is a GET request
+                    let first_new = endpoints_acc.len();
                     Endpoint::parse_from_dir_rec(
                         &ApiEndpointMethod::Get,
                         current_url,
@@ -147,13 +327,16 @@ impl EndpointsCollection {
                         guard_list,
                         endpoints_acc,
                     );
+                    endpoints_acc[first_new..]
+                        .iter_mut()
+                        .for_each(|e| e.websocket = true);
                     return;
                 }
 
                 let Some(new_current_url) = f
                     .file_name()
                     .to_str()
-                    .map(|fname| format!("{}/{}", current_url, fname))
+                    .map(|fname| format!("{}/{}", current_url, url_segment(fname)))
                 else {
                     return;
                 };
@@ -161,6 +344,21 @@ impl EndpointsCollection {
                 Self::parse_from_dir_rec(&new_current_url, &f_path, guard_list, endpoints_acc);
             });
     }
+
+    fn parse_method_dir(dir_name: &str) -> Option<ApiEndpointMethod> {
+        let method = match dir_name {
+            "GET" => ApiEndpointMethod::Get,
+            "POST" => ApiEndpointMethod::Post,
+            "PUT" => ApiEndpointMethod::Put,
+            "DELETE" => ApiEndpointMethod::Delete,
+            "PATCH" => ApiEndpointMethod::Patch,
+            "HEAD" => ApiEndpointMethod::Head,
+            "OPTIONS" => ApiEndpointMethod::Options,
+            _ => return None,
+        };
+
+        Some(method)
+    }
 }
 
 impl Endpoint {
@@ -192,6 +390,10 @@ impl Endpoint {
             .flat_map(|e| e.ok())
             .for_each(|f| {
                 let f_path = f.path();
+                // suites are never endpoints nor guards
+                if is_test_file(&f_path) {
+                    return;
+                }
 
                 if !f_path.is_dir() {
                     if !f_path.is_file() {
@@ -221,7 +423,7 @@ impl Endpoint {
                 let Some(new_current_url) = f
                     .file_name()
                     .to_str()
-                    .map(|fname| format!("{}/{}", current_url, fname))
+                    .map(|fname| format!("{}/{}", current_url, url_segment(fname)))
                 else {
                     return;
                 };
@@ -251,6 +453,16 @@ impl Endpoint {
         };
 
         let f_name = file.file_stem()?.to_str()?;
+        let url_path = format!("{}/{}", url_path, url_segment(f_name));
+
+        if !is_catch_all_last(&url_path) {
+            warn!(
+                "Endpoint {} has catch-all segment not at the end of url {}",
+                file.display(),
+                url_path
+            );
+            return None;
+        }
 
         let mut obj = Self {
             guards: guard_list
@@ -261,8 +473,10 @@ impl Endpoint {
             tag: tag.to_string(),
             method: method.clone(),
             yml_content: yml_content,
-            url_path: format!("{}/{}", url_path, f_name),
+            url_path,
             merged_declaration: "".into(),
+            websocket: false,
+            source: file.display().to_string(),
         };
 
         obj.merge_declaration();
@@ -270,6 +484,37 @@ impl Endpoint {
         Some(obj)
     }
 
+    fn add_path_params(&self, declaration: &mut YmlValue) {
+        let Some(path) = declaration
+            .get_mut("declaration")
+            .and_then(|d| d.get_mut("allowlist"))
+            .and_then(|a| a.get_mut("path"))
+            .and_then(|p| p.as_sequence_mut())
+        else {
+            return;
+        };
+
+        for param in path_params(&self.url_path) {
+            let is_declared = path
+                .iter()
+                .any(|p| p.get("field").and_then(|f| f.as_str()) == Some(param));
+            if is_declared {
+                continue;
+            }
+
+            path.push(YmlValue::Mapping(YmlMapping::from_iter([
+                (
+                    YmlValue::String("field".into()),
+                    YmlValue::String(param.into()),
+                ),
+                (
+                    YmlValue::String("type".into()),
+                    YmlValue::String("string".into()),
+                ),
+            ])));
+        }
+    }
+
     fn merge_declaration(&mut self) {
         // make it called declaration
         let mut declaration =
@@ -277,6 +522,7 @@ impl Endpoint {
         for guard in &self.guards {
             declaration = Self::merge_two_declarations(&declaration, &guard.yml_content);
         }
+        self.add_path_params(&mut declaration);
 
         self.merged_declaration = serde_yaml_ng::to_string(&declaration).unwrap_or("".into());
     }
@@ -294,12 +540,12 @@ impl Endpoint {
             }
             Some(v)
         });
-        // todo implement response
-        // todo implement path
         let mut description = "".to_string();
+        let mut path: Vec<YmlValue> = Vec::new();
         let mut params: Vec<YmlValue> = Vec::new();
         let mut headers: Vec<YmlValue> = Vec::new();
         let mut body = YmlValue::Null;
+        let mut responses = YmlMapping::new();
         for val in combined_map {
             if let Some(descr) = val.get("description").and_then(|f| f.as_str()) {
                 if description.len() > 0 {
@@ -309,6 +555,9 @@ impl Endpoint {
             }
 
             if let Some(al_list) = val.get("allowlist") {
+                if let Some(pt) = al_list.get("path").and_then(|p| p.as_sequence()) {
+                    path.extend(pt.iter().cloned());
+                }
                 if let Some(pm) = al_list.get("params").and_then(|p| p.as_sequence()) {
                     params.extend(pm.iter().map(|p| p.clone()));
                 }
@@ -326,10 +575,15 @@ impl Endpoint {
                     }
                 }
             }
+
+            if let Some(resp) = val.get("responses").and_then(|r| r.as_mapping()) {
+                Self::merge_responses(&mut responses, resp);
+            }
         }
 
         let headers = Self::remove_fields_duplicate(headers);
         let params = Self::remove_fields_duplicate(params);
+        let path = Self::remove_fields_duplicate(path);
 
         let declare_task = YmlValue::Mapping(YmlMapping::from_iter([
             (
@@ -343,6 +597,7 @@ impl Endpoint {
             (
                 YmlValue::String("allowlist".into()),
                 YmlValue::Mapping(YmlMapping::from_iter([
+                    (YmlValue::String("path".into()), YmlValue::Sequence(path)),
                     (
                         YmlValue::String("params".into()),
                         YmlValue::Sequence(params),
@@ -354,6 +609,10 @@ impl Endpoint {
                     (YmlValue::String("body".into()), body),
                 ])),
             ),
+            (
+                YmlValue::String("responses".into()),
+                YmlValue::Mapping(responses),
+            ),
         ]));
 
         YmlValue::Mapping(YmlMapping::from_iter([(
@@ -362,6 +621,44 @@ impl Endpoint {
         )]))
     }
 
+    /// responses are keyed by status code, body has the same syntax as `allowlist.body`
+    fn merge_responses(into: &mut YmlMapping, responses: &YmlMapping) {
+        for (status, response) in responses {
+            let status = match status {
+                YmlValue::Number(n) => n.to_string(),
+                YmlValue::String(s) => s.clone(),
+                _ => {
+                    warn!("bad response status: {:?}", status);
+                    continue;
+                }
+            };
+            let response = match response {
+                YmlValue::Null => YmlValue::Mapping(YmlMapping::new()),
+                r => r.clone(),
+            };
+            let key = YmlValue::String(status);
+
+            let merged = match into.get(&key) {
+                Some(existing) => {
+                    let mut merged = existing.clone();
+                    Self::merge_mappings_descriptions(existing, &response, &mut merged);
+                    let body = Self::merge_two_types(
+                        existing.get("body").unwrap_or(&YmlValue::Null),
+                        response.get("body").unwrap_or(&YmlValue::Null),
+                    );
+                    if let Some(m) = merged.as_mapping_mut()
+                        && !body.is_null()
+                    {
+                        m.insert(YmlValue::String("body".into()), body);
+                    }
+                    merged
+                }
+                None => response,
+            };
+            into.insert(key, merged);
+        }
+    }
+
     fn remove_fields_duplicate(to_remove: Vec<YmlValue>) -> Vec<YmlValue> {
         let mut new_vec = Vec::with_capacity(to_remove.len());
         let mut used_indices = Vec::with_capacity(to_remove.len());
@@ -611,7 +908,10 @@ impl Guard {
             return None;
         };
 
-        Some(Self { yml_content })
+        Some(Self {
+            yml_content,
+            source: guard_path.display().to_string(),
+        })
     }
 }
 
@@ -629,9 +929,47 @@ impl std::fmt::Display for EndpointsCollection {
     }
 }
 
+impl Endpoint {
+    /// method dir name the endpoint is parsed from
+    pub fn method_name(&self) -> String {
+        if self.websocket {
+            return "WS".to_string();
+        }
+        format!("{:?}", self.method).to_uppercase()
+    }
+
+    /// reason why the router cannot serve both endpoints
+    pub fn conflict_with(&self, other: &Endpoint) -> Option<String> {
+        if let Some((name, other_name)) = conflicting_captures(&self.url_path, &other.url_path) {
+            return Some(format!(
+                "capture {{{}}} of {} conflicts with {{{}}} of {}",
+                name, self.url_path, other_name, other.source
+            ));
+        }
+
+        if route_shape(&self.url_path) == route_shape(&other.url_path)
+            && self.method_name() == other.method_name()
+        {
+            return Some(format!(
+                "route {} {} is already defined by {}",
+                self.method_name(),
+                self.url_path,
+                other.source
+            ));
+        }
+
+        None
+    }
+}
+
 impl std::fmt::Display for Endpoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{{ method: {:?}, url: {} }}", self.method, self.url_path)
+        write!(
+            f,
+            "{{ method: {}, url: {} }}",
+            self.method_name(),
+            self.url_path
+        )
     }
 }
 
@@ -640,7 +978,14 @@ mod test {
     use rstmytype::ApiEndpointMethod;
     use serde_yaml_ng::{Mapping as YmlMapping, Value as YmlValue};
 
-    use crate::endpoints::parser::{Endpoint, EndpointsCollection};
+    use clap::Parser;
+
+    use crate::args::types::Args;
+    use crate::endpoints::parser::{
+        Endpoint, EndpointsCollection, Guard, conflicting_captures, is_catch_all_last, match_route,
+        path_params, route_shape, url_segment,
+    };
+    use crate::endpoints::{DslTree, build_router};
     #[test]
     fn test_merge_mappings_enum() {
         let left_val: YmlValue = serde_yaml_ng::from_str(
@@ -966,6 +1311,238 @@ mod test {
         assert_eq!(res.get("fields").unwrap().as_sequence().unwrap().len(), 3);
     }
 
+    #[test]
+    fn test_parse_method_dir() {
+        assert_eq!(
+            EndpointsCollection::parse_method_dir("GET"),
+            Some(ApiEndpointMethod::Get)
+        );
+        assert_eq!(
+            EndpointsCollection::parse_method_dir("PUT"),
+            Some(ApiEndpointMethod::Put)
+        );
+        assert_eq!(
+            EndpointsCollection::parse_method_dir("DELETE"),
+            Some(ApiEndpointMethod::Delete)
+        );
+        assert_eq!(
+            EndpointsCollection::parse_method_dir("PATCH"),
+            Some(ApiEndpointMethod::Patch)
+        );
+        assert_eq!(
+            EndpointsCollection::parse_method_dir("HEAD"),
+            Some(ApiEndpointMethod::Head)
+        );
+        assert_eq!(
+            EndpointsCollection::parse_method_dir("OPTIONS"),
+            Some(ApiEndpointMethod::Options)
+        );
+        assert_eq!(EndpointsCollection::parse_method_dir("get"), None);
+        assert_eq!(EndpointsCollection::parse_method_dir("TEMPLATES"), None);
+    }
+
+    #[test]
+    fn test_url_segment() {
+        assert_eq!(url_segment("[id]"), "{id}");
+        assert_eq!(url_segment("{id}"), "{id}");
+        assert_eq!(url_segment("orders"), "orders");
+        assert_eq!(url_segment("[id"), "[id");
+        assert_eq!(url_segment("[...rest]"), "{*rest}");
+    }
+
+    #[test]
+    fn test_is_catch_all_last() {
+        assert!(is_catch_all_last("/proxy/{*rest}"));
+        assert!(is_catch_all_last("/proxy/{id}"));
+        assert!(!is_catch_all_last("/proxy/{*rest}/some"));
+    }
+
+    #[test]
+    fn test_conflicting_captures() {
+        assert_eq!(
+            route_shape("/users/{id}/files/{*rest}"),
+            "/users/{}/files/{*}"
+        );
+        assert_eq!(
+            conflicting_captures("/users/{id}", "/users/{userId}/posts"),
+            Some(("id", "userId"))
+        );
+        assert_eq!(
+            conflicting_captures("/users/{id}", "/users/{id}/posts"),
+            None
+        );
+        assert_eq!(conflicting_captures("/users/{id}", "/users/{*rest}"), None);
+        assert_eq!(conflicting_captures("/users/{id}", "/groups/{name}"), None);
+    }
+
+    #[test]
+    fn test_conflicting_endpoints_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "GET/users/[id].yml",
+            "GET/users/{id}.yml",
+            "GET/users/[id]/posts.yml",
+            "PUT/users/[userId].yml",
+            "DELETE/users/[id].yml",
+        ] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "ret:\n  return: ok\n").unwrap();
+        }
+        let dsl_path = dir.path().to_str().unwrap();
+
+        assert_eq!(
+            EndpointsCollection::parse_all_from_dir(dsl_path)
+                .endpoints
+                .len(),
+            5
+        );
+        let collection = EndpointsCollection::parse_from_dir(dsl_path);
+        let mut sources: Vec<_> = collection
+            .endpoints
+            .iter()
+            .map(|e| e.source.strip_prefix(dsl_path).unwrap())
+            .collect();
+        sources.sort();
+        assert_eq!(
+            sources,
+            vec![
+                "/DELETE/users/[id].yml",
+                "/GET/users/[id].yml",
+                "/GET/users/[id]/posts.yml"
+            ]
+        );
+
+        // router is built without panics
+        let args = Args::parse_from(["rstrouter", "--dsl-path", dsl_path, "--disable-swagger"]);
+        let _router = build_router(&args, &DslTree::parse(dsl_path), axum::Router::new());
+    }
+
+    #[test]
+    fn test_path_params() {
+        assert_eq!(
+            path_params("/users/{id}/orders/{order}"),
+            vec!["id", "order"]
+        );
+        assert!(path_params("/users/orders").is_empty());
+        assert_eq!(path_params("/proxy/{id}/{*rest}"), vec!["id", "rest"]);
+    }
+
+    #[test]
+    fn test_merge_declaration_with_path() {
+        let mut endpoint = Endpoint {
+            guards: vec![],
+            tag: "/users".to_string(),
+            url_path: "/users/{id}/orders/{order}".to_string(),
+            method: ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                    declaration:
+                      call: declare
+                      allowlist:
+                        path:
+                          - field: id
+                            type: number
+                            description: user id
+                "#,
+            )
+            .unwrap(),
+            merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
+        };
+
+        endpoint.merge_declaration();
+
+        let declaration: YmlValue = serde_yaml_ng::from_str(&endpoint.merged_declaration).unwrap();
+        let path = declaration
+            .get("declaration")
+            .and_then(|d| d.get("allowlist"))
+            .and_then(|a| a.get("path"))
+            .and_then(|p| p.as_sequence())
+            .unwrap();
+
+        assert_eq!(path.len(), 2);
+        assert_eq!(path[0].get("field").unwrap(), "id");
+        assert_eq!(path[0].get("type").unwrap(), "number");
+        assert_eq!(path[1].get("field").unwrap(), "order");
+        assert_eq!(path[1].get("type").unwrap(), "string");
+    }
+
+    #[test]
+    fn test_merge_responses_with_guards() {
+        let mut endpoint = Endpoint {
+            guards: vec![Guard {
+                yml_content: serde_yaml_ng::from_str(
+                    r#"
+                        declaration:
+                          call: declare
+                          responses:
+                            401:
+                              description: no token
+                            200:
+                              description: guarded
+                    "#,
+                )
+                .unwrap(),
+                source: "".into(),
+            }],
+            tag: "/users".to_string(),
+            url_path: "/users".to_string(),
+            method: ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                    declaration:
+                      call: declare
+                      responses:
+                        200:
+                          description: user
+                          body:
+                            - field: id
+                              type: number
+                "#,
+            )
+            .unwrap(),
+            merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
+        };
+
+        endpoint.merge_declaration();
+
+        let declaration: YmlValue = serde_yaml_ng::from_str(&endpoint.merged_declaration).unwrap();
+        let responses = declaration
+            .get("declaration")
+            .and_then(|d| d.get("responses"))
+            .unwrap();
+
+        assert_eq!(responses.as_mapping().unwrap().len(), 2);
+        let ok = responses.get("200").unwrap();
+        assert_eq!(ok.get("description").unwrap(), "user; guarded");
+        assert_eq!(ok.get("body").unwrap().as_sequence().unwrap().len(), 1);
+        assert_eq!(
+            responses.get("401").unwrap().get("description").unwrap(),
+            "no token"
+        );
+    }
+
+    #[test]
+    fn test_match_route() {
+        let m = match_route("/users/{id}", "/users/42").unwrap();
+        assert_eq!(m.params["id"], "42");
+        assert_eq!(m.static_segments, 1);
+
+        let m = match_route("/users/{id}", "/users/{id}").unwrap();
+        assert!(m.params.is_empty());
+
+        let m = match_route("/files/{*path}", "/files/a/b.txt").unwrap();
+        assert_eq!(m.params["path"], "a/b.txt");
+
+        assert!(match_route("/users/{id}", "/users").is_none());
+        assert!(match_route("/users/{id}", "/users/42/posts").is_none());
+        assert!(match_route("/users/{id}", "/groups/42").is_none());
+    }
+
     #[test]
     fn smoke_test_fold_is_parsing() {
         let endp = EndpointsCollection::parse_from_dir("./unittest_dsl");
@@ -0,0 +1,287 @@
+use std::fs::{read_dir, read_to_string};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::{
+    Router,
+    extract::Request,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use log::{info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde_yaml_ng::Value as YmlValue;
+use tower::ServiceExt;
+
+use crate::args::types::Args;
+use crate::endpoints::parser::EndpointsCollection;
+use crate::endpoints::{DslTree, RouteInfo, build_router};
+use crate::engine::lint::strict_check;
+
+/// events from editors come in bursts, reload happens once the burst is over
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// router built from DSL dir which can be rebuilt at runtime.
+/// Every request is served by a clone of the router taken on arrival,
+/// so in-flight requests finish on the version they started with
+pub struct ReloadableRouter {
+    args: Args,
+    current: RwLock<LoadedDsl>,
+    /// overlapping reloads would swap in whichever build finishes last
+    reloading: tokio::sync::Mutex<()>,
+}
+
+#[derive(Clone)]
+struct LoadedDsl {
+    router: Router,
+    routes: Arc<Vec<RouteInfo>>,
+    collection: Arc<EndpointsCollection>,
+}
+
+impl LoadedDsl {
+    fn load(args: &Args) -> Self {
+        let tree = DslTree::parse(&args.dsl_path);
+        Self {
+            router: build_router(args, &tree, Router::new()),
+            routes: Arc::new(tree.routes()),
+            collection: Arc::new(tree.collection),
+        }
+    }
+}
+
+fn is_yml_file(path: &Path) -> bool {
+    let is_guard = path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .is_some_and(|f| f.starts_with(".guard"));
+    let is_yml = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e == "yml" || e == "yaml");
+
+    is_guard || is_yml
+}
+
+/// every yml file of DSL dir which cannot be read or parsed, with the reason
+pub fn bad_yml_files(dir: &PathBuf) -> Vec<(PathBuf, String)> {
+    let entries = match read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return vec![(dir.clone(), e.to_string())],
+    };
+
+    let mut errors = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != "STATIC" {
+                errors.extend(bad_yml_files(&path));
+            }
+            continue;
+        }
+
+        if !is_yml_file(&path) {
+            continue;
+        }
+
+        let parsed = read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| serde_yaml_ng::from_str::<YmlValue>(&c).map_err(|e| e.to_string()));
+        if let Err(e) = parsed {
+            errors.push((path, e));
+        }
+    }
+
+    errors
+}
+
+/// parser skips bad files with a warning, reload must not drop endpoints silently
+fn check_yml_files(dir: &PathBuf) -> Result<(), String> {
+    match bad_yml_files(dir).into_iter().next() {
+        Some((path, e)) => Err(format!("{}: {}", path.display(), e)),
+        None => Ok(()),
+    }
+}
+
+impl ReloadableRouter {
+    pub fn new(args: &Args) -> Arc<Self> {
+        Arc::new(Self {
+            args: args.clone(),
+            current: RwLock::new(LoadedDsl::load(args)),
+            reloading: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// reads and parses the whole DSL dir, so has to run off the async workers
+    fn rebuild(args: &Args) -> Result<LoadedDsl, String> {
+        check_yml_files(&PathBuf::from(&args.dsl_path))?;
+        if args.strict {
+            strict_check(&args.dsl_path)?;
+        }
+
+        // axum panics on conflicting routes
+        catch_unwind(AssertUnwindSafe(|| LoadedDsl::load(args)))
+            .map_err(|_| "cannot build router from DSL dir".to_string())
+    }
+
+    /// on failure previous router is kept
+    pub async fn reload(&self) -> Result<(), String> {
+        let _reloading = self.reloading.lock().await;
+
+        let args = self.args.clone();
+        let loaded = tokio::task::spawn_blocking(move || Self::rebuild(&args))
+            .await
+            .map_err(|e| format!("DSL reload task failed: {}", e))??;
+
+        let mut current = self
+            .current
+            .write()
+            .map_err(|_| "router lock is poisoned".to_string())?;
+        *current = loaded;
+
+        Ok(())
+    }
+
+    fn current(&self) -> Option<LoadedDsl> {
+        self.current.read().ok().map(|r| r.clone())
+    }
+
+    pub fn routes(&self) -> Arc<Vec<RouteInfo>> {
+        self.current().map(|c| c.routes).unwrap_or_default()
+    }
+
+    /// endpoints of the version being served
+    pub fn collection(&self) -> Option<Arc<EndpointsCollection>> {
+        self.current().map(|c| c.collection)
+    }
+
+    async fn serve(self: Arc<Self>, request: Request) -> Response {
+        let Some(LoadedDsl { router, .. }) = self.current() else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        let Ok(response) = router.oneshot(request).await;
+        response
+    }
+
+    pub fn into_router(self: Arc<Self>, app: Router) -> Router {
+        app.fallback(|q: Request| self.serve(q))
+    }
+
+    /// returned watcher has to be kept alive for the whole server lifetime
+    pub fn watch(self: &Arc<Self>) -> Option<RecommendedWatcher> {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if !event.kind.is_access() => {
+                    sender.send(()).ok();
+                }
+                Ok(_) => {}
+                Err(e) => warn!("DSL watch error: {}", e),
+            })
+            .map_err(|e| warn!("cannot create DSL dir watcher: {}", e))
+            .ok()?;
+
+        watcher
+            .watch(Path::new(&self.args.dsl_path), RecursiveMode::Recursive)
+            .map_err(|e| warn!("cannot watch {}: {}", self.args.dsl_path, e))
+            .ok()?;
+
+        let reloadable = self.clone();
+        tokio::spawn(async move {
+            while receiver.recv().await.is_some() {
+                tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                while receiver.try_recv().is_ok() {}
+
+                match reloadable.reload().await {
+                    Ok(()) => info!("DSL dir {} reloaded", reloadable.args.dsl_path),
+                    Err(e) => warn!("DSL reload failed, keeping previous version: {}", e),
+                }
+            }
+        });
+
+        info!("Watching {} for changes", self.args.dsl_path);
+        Some(watcher)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, write};
+    use std::path::PathBuf;
+
+    use axum::{
+        body::{Body, to_bytes},
+        http::Request,
+    };
+    use clap::Parser;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    use crate::args::types::Args;
+    use crate::endpoints::reload::{ReloadableRouter, check_yml_files};
+
+    /// removed when dropped
+    fn temp_dsl() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        create_dir_all(dir.path().join("api/GET")).unwrap();
+        dir
+    }
+
+    async fn get_body(router: axum::Router, uri: &str) -> (u16, String) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = router.oneshot(req).await.unwrap();
+        let status = resp.status().as_u16();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn test_check_yml_files() {
+        assert!(check_yml_files(&PathBuf::from("./unittest_dsl")).is_ok());
+
+        let dir = temp_dsl();
+        write(dir.path().join("api/GET/bad.yml"), "a: [").unwrap();
+        assert!(check_yml_files(&dir.path().to_path_buf()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let tmp = temp_dsl();
+        let dir = tmp.path();
+        write(dir.join("api/GET/hello.yml"), "test:\n  return: one\n").unwrap();
+
+        let args = Args::parse_from([
+            "rstrouter",
+            "--dsl-path",
+            dir.to_str().unwrap(),
+            "--disable-swagger",
+        ]);
+        let reloadable = ReloadableRouter::new(&args);
+        let router = reloadable.clone().into_router(axum::Router::new());
+
+        let res = get_body(router.clone(), "/api/hello").await;
+        assert_eq!(res, (200, r#"{"response":"one"}"#.to_string()));
+        assert_eq!(reloadable.routes().len(), 1);
+
+        write(dir.join("api/GET/hello.yml"), "test:\n  return: two\n").unwrap();
+        write(dir.join("api/GET/new.yml"), "test:\n  return: new\n").unwrap();
+        let (first, second) = tokio::join!(reloadable.reload(), reloadable.reload());
+        assert!(first.is_ok() && second.is_ok());
+
+        let res = get_body(router.clone(), "/api/hello").await;
+        assert_eq!(res, (200, r#"{"response":"two"}"#.to_string()));
+        let res = get_body(router.clone(), "/api/new").await;
+        assert_eq!(res.0, 200);
+        assert_eq!(reloadable.routes().len(), 2);
+
+        // broken file keeps previous version
+        write(dir.join("api/GET/hello.yml"), "test: [").unwrap();
+        assert!(reloadable.reload().await.is_err());
+
+        let res = get_body(router, "/api/hello").await;
+        assert_eq!(res, (200, r#"{"response":"two"}"#.to_string()));
+    }
+}
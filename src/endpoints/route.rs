@@ -1,36 +1,126 @@
 use std::sync::Arc;
 
-use axum::{extract::Request, routing::MethodRouter};
+use axum::{
+    extract::{FromRequestParts, Request, ws::WebSocketUpgrade},
+    http::{Method, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{MethodFilter, MethodRouter},
+};
+use itertools::Itertools;
 use rstmytype::ApiEndpointMethod;
 
 use crate::endpoints::parser::Endpoint;
 use crate::endpoints::types::Request as LocalRequest;
-use crate::engine::Engine;
+use crate::engine::{Engine, EngineConfig};
 
-pub fn get_route(chunk: Vec<&Endpoint>, dsl_path: &str) -> MethodRouter {
+fn http_method(method: &ApiEndpointMethod) -> Option<Method> {
+    let method = match method {
+        ApiEndpointMethod::Get => Method::GET,
+        ApiEndpointMethod::Post => Method::POST,
+        ApiEndpointMethod::Put => Method::PUT,
+        ApiEndpointMethod::Delete => Method::DELETE,
+        ApiEndpointMethod::Patch => Method::PATCH,
+        ApiEndpointMethod::Head => Method::HEAD,
+        ApiEndpointMethod::Options => Method::OPTIONS,
+        _ => return None,
+    };
+
+    Some(method)
+}
+
+fn allow_header_value(methods: &[Method]) -> String {
+    let mut allowed: Vec<&str> = methods.iter().map(|m| m.as_str()).collect();
+    if methods.contains(&Method::GET) {
+        // axum answers HEAD with the GET handler when no HEAD endpoint is declared
+        allowed.push(Method::HEAD.as_str());
+    }
+    allowed.push(Method::OPTIONS.as_str());
+
+    allowed.into_iter().unique().join(", ")
+}
+
+async fn handle(engine: Arc<Engine>, q: Request) -> Response {
+    let request = LocalRequest::from_request(q).await;
+    if engine.is_streaming() {
+        return engine.execute_stream(request).await;
+    }
+
+    engine.execute(request).await.into_response()
+}
+
+/// plain GET requests on websocket url are served by GET endpoint if it exists
+async fn handle_websocket(
+    engine: Arc<Engine>,
+    fallback: Option<Arc<Engine>>,
+    q: Request,
+) -> Response {
+    let (mut parts, body) = q.into_parts();
+    match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+        Ok(upgrade) => {
+            let request = LocalRequest::from_request(Request::from_parts(parts, body)).await;
+            engine.execute_websocket(upgrade, request).await
+        }
+        Err(rejection) => match fallback {
+            Some(fallback) => handle(fallback, Request::from_parts(parts, body)).await,
+            None => rejection.into_response(),
+        },
+    }
+}
+
+pub fn get_route(chunk: Vec<&Endpoint>, config: &EngineConfig) -> MethodRouter {
     let mut method_router = MethodRouter::new();
+    let mut methods = Vec::with_capacity(chunk.len());
+
+    let websocket = chunk
+        .iter()
+        .find(|e| e.websocket)
+        .map(|e| Arc::new(Engine::from_endpoint(e, config)));
+    let mut websocket_fallback = None;
 
-    for endpoint in chunk {
-        let engine = Arc::new(Engine::from_endpoint(endpoint, dsl_path));
-
-        if endpoint.method == ApiEndpointMethod::Get {
-            method_router = method_router.get(|q: Request| async move {
-                engine.execute(LocalRequest::from_request(q).await).await
-            })
-        } else if endpoint.method == ApiEndpointMethod::Post {
-            method_router = method_router.post(|q: Request| async move {
-                engine.execute(LocalRequest::from_request(q).await).await
-            })
+    for endpoint in chunk.iter().filter(|e| !e.websocket) {
+        let Some(method) = http_method(&endpoint.method) else {
+            continue;
+        };
+        let Ok(filter) = MethodFilter::try_from(method.clone()) else {
+            continue;
+        };
+
+        let engine = Arc::new(Engine::from_endpoint(endpoint, config));
+        methods.push(method.clone());
+
+        if websocket.is_some() && method == Method::GET {
+            websocket_fallback = Some(engine);
+            continue;
+        }
+
+        method_router = method_router.on(filter, |q: Request| handle(engine, q));
+    }
+
+    if let Some(engine) = websocket {
+        method_router =
+            method_router.get(|q: Request| handle_websocket(engine, websocket_fallback, q));
+        if !methods.contains(&Method::GET) {
+            methods.push(Method::GET);
         }
     }
 
+    if !methods.contains(&Method::OPTIONS) {
+        let allow = allow_header_value(&methods);
+        method_router = method_router
+            .options(|| async move { (StatusCode::NO_CONTENT, [(header::ALLOW, allow)]) });
+    }
+
     method_router
 }
 
 #[cfg(test)]
 mod test {
     use crate::endpoints::{parser::Endpoint, route::get_route};
-    use axum::{handler::Handler, http::Request};
+    use crate::engine::EngineConfig;
+    use axum::{
+        handler::Handler,
+        http::{Request, header},
+    };
 
     #[tokio::test]
     async fn test_get_route() {
@@ -48,6 +138,8 @@ mod test {
                 )
                 .unwrap(),
                 merged_declaration: "".into(),
+                websocket: false,
+                source: "".into(),
             },
             Endpoint {
                 guards: vec![],
@@ -62,11 +154,13 @@ mod test {
                 )
                 .unwrap(),
                 merged_declaration: "".into(),
+                websocket: false,
+                source: "".into(),
             },
         ];
 
         let endpoitns = endpoints_owned.iter().collect();
-        let r = get_route(endpoitns, "./unittest_dsl");
+        let r = get_route(endpoitns, &EngineConfig::new("./unittest_dsl"));
 
         let req = Request::builder()
             .uri("/some/")
@@ -84,4 +178,244 @@ mod test {
             .unwrap();
         r.call(req, ()).await;
     }
+
+    #[tokio::test]
+    async fn test_get_route_other_methods() {
+        let endpoints_owned: Vec<Endpoint> = [
+            rstmytype::ApiEndpointMethod::Put,
+            rstmytype::ApiEndpointMethod::Delete,
+            rstmytype::ApiEndpointMethod::Patch,
+        ]
+        .into_iter()
+        .map(|method| Endpoint {
+            guards: vec![],
+            tag: "some".to_string(),
+            url_path: "/some/".to_string(),
+            method,
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                  test:
+                    return: ok
+                    status: 201
+                "#,
+            )
+            .unwrap(),
+            merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
+        })
+        .collect();
+
+        let r = get_route(
+            endpoints_owned.iter().collect(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        for method in ["PUT", "DELETE", "PATCH"] {
+            let req = Request::builder()
+                .uri("/some/")
+                .method(method)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let resp = r.clone().call(req, ()).await;
+            assert_eq!(resp.status().as_u16(), 201);
+        }
+
+        let req = Request::builder()
+            .uri("/some/")
+            .method("GET")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = r.clone().call(req, ()).await;
+        assert_eq!(resp.status().as_u16(), 405);
+
+        let req = Request::builder()
+            .uri("/some/")
+            .method("OPTIONS")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = r.call(req, ()).await;
+        assert_eq!(resp.status().as_u16(), 204);
+        assert_eq!(
+            resp.headers().get(header::ALLOW).unwrap(),
+            "PUT, DELETE, PATCH, OPTIONS"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_route_custom_options() {
+        let endpoints_owned = [
+            Endpoint {
+                guards: vec![],
+                tag: "some".to_string(),
+                url_path: "/some/".to_string(),
+                method: rstmytype::ApiEndpointMethod::Get,
+                yml_content: serde_yaml_ng::from_str(
+                    r#"
+                      test:
+                        return: ok get
+                    "#,
+                )
+                .unwrap(),
+                merged_declaration: "".into(),
+                websocket: false,
+                source: "".into(),
+            },
+            Endpoint {
+                guards: vec![],
+                tag: "some".to_string(),
+                url_path: "/some/".to_string(),
+                method: rstmytype::ApiEndpointMethod::Options,
+                yml_content: serde_yaml_ng::from_str(
+                    r#"
+                      test:
+                        return: ok options
+                        status: 200
+                    "#,
+                )
+                .unwrap(),
+                merged_declaration: "".into(),
+                websocket: false,
+                source: "".into(),
+            },
+        ];
+
+        let r = get_route(
+            endpoints_owned.iter().collect(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let req = Request::builder()
+            .uri("/some/")
+            .method("HEAD")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = r.clone().call(req, ()).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let req = Request::builder()
+            .uri("/some/")
+            .method("OPTIONS")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = r.call(req, ()).await;
+        assert_eq!(resp.status().as_u16(), 200);
+    }
+
+    fn websocket_endpoints() -> Vec<Endpoint> {
+        vec![
+            Endpoint {
+                guards: vec![],
+                tag: "some".to_string(),
+                url_path: "/ws".to_string(),
+                method: rstmytype::ApiEndpointMethod::Get,
+                yml_content: serde_yaml_ng::from_str(
+                    r#"
+                      count:
+                        assign:
+                          counter: '${typeof counter === "undefined" ? 1 : counter + 1}'
+                      check:
+                        switch:
+                          - condition: ${message === "bye"}
+                            next: bye
+                      reply:
+                        send:
+                          echo: ${message}
+                          counter: ${counter}
+                        next: end
+                      bye:
+                        close: 4000
+                        reason: bye
+                    "#,
+                )
+                .unwrap(),
+                merged_declaration: "".into(),
+                websocket: true,
+                source: "".into(),
+            },
+            Endpoint {
+                guards: vec![],
+                tag: "some".to_string(),
+                url_path: "/ws".to_string(),
+                method: rstmytype::ApiEndpointMethod::Get,
+                yml_content: serde_yaml_ng::from_str(
+                    r#"
+                      test:
+                        return: ok get
+                    "#,
+                )
+                .unwrap(),
+                merged_declaration: "".into(),
+                websocket: false,
+                source: "".into(),
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_get_route_websocket() {
+        use futures::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let endpoints_owned = websocket_endpoints();
+        let app = axum::Router::new().route(
+            "/ws",
+            get_route(
+                endpoints_owned.iter().collect(),
+                &EngineConfig::new("./unittest_dsl"),
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr))
+            .await
+            .unwrap();
+
+        socket.send(Message::text("hello")).await.unwrap();
+        let reply = socket.next().await.unwrap().unwrap();
+        assert_eq!(reply.to_text().unwrap(), r#"{"counter":1,"echo":"hello"}"#);
+
+        // js context is kept between messages
+        socket.send(Message::text(r#"{"a": 1}"#)).await.unwrap();
+        let reply = socket.next().await.unwrap().unwrap();
+        assert_eq!(reply.to_text().unwrap(), r#"{"counter":2,"echo":{"a":1}}"#);
+
+        socket.send(Message::text("bye")).await.unwrap();
+        let Message::Close(Some(frame)) = socket.next().await.unwrap().unwrap() else {
+            panic!("expected close frame");
+        };
+        assert_eq!(u16::from(frame.code), 4000);
+        assert_eq!(frame.reason.as_str(), "bye");
+    }
+
+    #[tokio::test]
+    async fn test_get_route_websocket_plain_get() {
+        let endpoints_owned = websocket_endpoints();
+        let r = get_route(
+            endpoints_owned.iter().collect(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let req = Request::builder()
+            .uri("/ws")
+            .method("GET")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = r.clone().call(req, ()).await;
+        assert_eq!(resp.status().as_u16(), 200);
+
+        let r = get_route(
+            endpoints_owned[..1].iter().collect(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+        let req = Request::builder()
+            .uri("/ws")
+            .method("GET")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = r.call(req, ()).await;
+        assert!(resp.status().is_client_error());
+    }
 }
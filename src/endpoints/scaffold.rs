@@ -0,0 +1,656 @@
+use std::path::PathBuf;
+
+use log::warn;
+use serde_yaml_ng::{Mapping as YmlMapping, Value as YmlValue};
+
+/// OpenAPI operation keys and method dirs they are generated into
+const METHODS: [(&str, &str); 7] = [
+    ("get", "GET"),
+    ("post", "POST"),
+    ("put", "PUT"),
+    ("delete", "DELETE"),
+    ("patch", "PATCH"),
+    ("head", "HEAD"),
+    ("options", "OPTIONS"),
+];
+
+/// recursive schemas are cut at this depth
+const MAX_DEPTH: usize = 8;
+
+/// endpoint file relative to DSL dir
+#[derive(Debug)]
+pub struct SkeletonFile {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+fn key(name: &str) -> YmlValue {
+    YmlValue::String(name.to_string())
+}
+
+fn mapping<const N: usize>(entries: [(&str, YmlValue); N]) -> YmlMapping {
+    YmlMapping::from_iter(entries.into_iter().map(|(k, v)| (key(k), v)))
+}
+
+/// `/users/{id}` becomes `GET/users/[id].yml`, the same layout the parser reads
+fn file_path(method_dir: &str, url: &str) -> Option<PathBuf> {
+    let segments: Vec<String> = url
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(
+            |s| match s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(param) => format!("[{}]", param),
+                None => s.to_string(),
+            },
+        )
+        .collect();
+
+    let (last, dirs) = segments.split_last()?;
+    if segments
+        .iter()
+        .any(|s| s == "." || s == ".." || s.contains('\\'))
+    {
+        return None;
+    }
+
+    let mut path = PathBuf::from(method_dir);
+    dirs.iter().for_each(|d| path.push(d));
+    path.push(format!("{}.yml", last));
+    Some(path)
+}
+
+struct Generator<'a> {
+    spec: &'a YmlValue,
+}
+
+impl Generator<'_> {
+    /// local `$ref` only, e.g. `#/components/schemas/User`
+    fn resolve(&self, value: &YmlValue, depth: usize) -> Option<YmlValue> {
+        let Some(reference) = value.get("$ref").and_then(|r| r.as_str()) else {
+            return Some(value.clone());
+        };
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        let Some(pointer) = reference.strip_prefix("#/") else {
+            warn!("external reference {} is not supported", reference);
+            return None;
+        };
+        let mut target = self.spec;
+        for part in pointer.split('/') {
+            target = target.get(part.replace("~1", "/").replace("~0", "~"))?;
+        }
+
+        self.resolve(target, depth + 1)
+    }
+
+    fn schema_kind(schema: &YmlValue) -> String {
+        let kind = match schema.get("type") {
+            Some(YmlValue::String(kind)) => Some(kind.as_str()),
+            // 3.1 nullable types are written as a list
+            Some(YmlValue::Sequence(kinds)) => {
+                kinds.iter().flat_map(|k| k.as_str()).find(|k| *k != "null")
+            }
+            _ => None,
+        };
+
+        match kind {
+            Some(kind) => kind.to_string(),
+            None if schema.get("properties").is_some() => "object".to_string(),
+            None if schema.get("items").is_some() => "array".to_string(),
+            None => "string".to_string(),
+        }
+    }
+
+    /// `allOf` parts are merged, first variant of `oneOf`/`anyOf` is used
+    fn flatten(&self, schema: &YmlValue, depth: usize) -> YmlValue {
+        let Some(schema) = self.resolve(schema, depth) else {
+            return YmlValue::Null;
+        };
+
+        if let Some(parts) = schema.get("allOf").and_then(|a| a.as_sequence()) {
+            let mut properties = YmlMapping::new();
+            let mut required = Vec::new();
+            for part in parts {
+                let part = self.flatten(part, depth + 1);
+                if let Some(p) = part.get("properties").and_then(|p| p.as_mapping()) {
+                    properties.extend(p.clone());
+                }
+                if let Some(r) = part.get("required").and_then(|r| r.as_sequence()) {
+                    required.extend(r.iter().cloned());
+                }
+            }
+            return YmlValue::Mapping(mapping([
+                ("type", key("object")),
+                ("properties", YmlValue::Mapping(properties)),
+                ("required", YmlValue::Sequence(required)),
+            ]));
+        }
+
+        let variant = ["oneOf", "anyOf"]
+            .iter()
+            .flat_map(|k| schema.get(k)?.as_sequence()?.first())
+            .next();
+        match variant {
+            Some(variant) => self.flatten(variant, depth + 1),
+            None => schema,
+        }
+    }
+
+    /// field/type syntax of `allowlist.body`
+    fn dsl_type(&self, schema: &YmlValue, depth: usize) -> YmlMapping {
+        let schema = self.flatten(schema, depth);
+        let kind = Self::schema_kind(&schema);
+        let mut dsl_type = mapping([("type", key(&kind))]);
+
+        if let Some(description) = schema.get("description").filter(|d| d.is_string()) {
+            dsl_type.insert(key("description"), description.clone());
+        }
+        if let Some(values) = schema.get("enum").filter(|e| e.is_sequence()) {
+            dsl_type.insert(key("enum"), values.clone());
+        }
+        if depth > MAX_DEPTH {
+            return dsl_type;
+        }
+
+        if kind == "object"
+            && let Some(properties) = schema.get("properties").and_then(|p| p.as_mapping())
+        {
+            let required = schema.get("required").and_then(|r| r.as_sequence());
+            let fields = properties
+                .iter()
+                .flat_map(|(name, property)| {
+                    let is_required = required.is_some_and(|r| r.contains(name));
+                    Some(self.field(name.as_str()?, property, is_required, depth + 1))
+                })
+                .collect();
+            dsl_type.insert(key("fields"), YmlValue::Sequence(fields));
+        }
+
+        if kind == "array"
+            && let Some(items) = schema.get("items")
+        {
+            let items = self.dsl_type(items, depth + 1);
+            dsl_type.insert(key("items"), YmlValue::Mapping(items));
+        }
+
+        dsl_type
+    }
+
+    fn field(&self, name: &str, schema: &YmlValue, required: bool, depth: usize) -> YmlValue {
+        let mut field = mapping([("field", key(name))]);
+        field.extend(self.dsl_type(schema, depth));
+        if required {
+            field.insert(key("required"), YmlValue::Bool(true));
+        }
+        YmlValue::Mapping(field)
+    }
+
+    fn example(&self, schema: &YmlValue, depth: usize) -> YmlValue {
+        let schema = self.flatten(schema, depth);
+        if let Some(example) = schema.get("example") {
+            return example.clone();
+        }
+        if let Some(example) = schema
+            .get("examples")
+            .and_then(|e| e.as_sequence())
+            .and_then(|e| e.first())
+        {
+            return example.clone();
+        }
+        if let Some(value) = schema.get("default") {
+            return value.clone();
+        }
+        if let Some(value) = schema
+            .get("enum")
+            .and_then(|e| e.as_sequence())
+            .and_then(|e| e.first())
+        {
+            return value.clone();
+        }
+        if depth > MAX_DEPTH {
+            return YmlValue::Null;
+        }
+
+        match Self::schema_kind(&schema).as_str() {
+            "object" => YmlValue::Mapping(
+                schema
+                    .get("properties")
+                    .and_then(|p| p.as_mapping())
+                    .iter()
+                    .flat_map(|p| p.iter())
+                    .map(|(name, property)| (name.clone(), self.example(property, depth + 1)))
+                    .collect(),
+            ),
+            "array" => YmlValue::Sequence(
+                schema
+                    .get("items")
+                    .map(|items| self.example(items, depth + 1))
+                    .into_iter()
+                    .collect(),
+            ),
+            "integer" | "number" => YmlValue::Number(0.into()),
+            "boolean" => YmlValue::Bool(true),
+            _ => key(match schema.get("format").and_then(|f| f.as_str()) {
+                Some("date-time") => "1970-01-01T00:00:00Z",
+                Some("date") => "1970-01-01",
+                Some("uuid") => "00000000-0000-0000-0000-000000000000",
+                Some("email") => "user@example.com",
+                _ => "string",
+            }),
+        }
+    }
+
+    /// json content is preferred, otherwise the first declared media type
+    fn media(&self, body: &YmlValue) -> Option<YmlValue> {
+        let content = self.resolve(body, 0)?.get("content")?.as_mapping()?.clone();
+        content
+            .get("application/json")
+            .or_else(|| content.values().next())
+            .cloned()
+    }
+
+    fn media_example(&self, media: &YmlValue) -> YmlValue {
+        if let Some(example) = media.get("example") {
+            return example.clone();
+        }
+        if let Some(example) = media
+            .get("examples")
+            .and_then(|e| e.as_mapping())
+            .and_then(|e| e.values().next())
+            .and_then(|e| self.resolve(e, 0))
+            .and_then(|e| e.get("value").cloned())
+        {
+            return example;
+        }
+
+        media
+            .get("schema")
+            .map(|s| self.example(s, 0))
+            .unwrap_or(YmlValue::Null)
+    }
+
+    fn allowlist(&self, shared_params: &[YmlValue], operation: &YmlValue) -> YmlMapping {
+        let operation_params = operation
+            .get("parameters")
+            .and_then(|p| p.as_sequence())
+            .map(|p| p.as_slice())
+            .unwrap_or_default();
+
+        let mut path = Vec::new();
+        let mut params = Vec::new();
+        let mut headers = Vec::new();
+        let mut seen = Vec::new();
+        // operation parameters override path item ones
+        for param in operation_params.iter().chain(shared_params) {
+            let Some(param) = self.resolve(param, 0) else {
+                continue;
+            };
+            let (Some(name), Some(location)) = (
+                param.get("name").and_then(|n| n.as_str()),
+                param.get("in").and_then(|i| i.as_str()),
+            ) else {
+                continue;
+            };
+            if seen.contains(&(name.to_string(), location.to_string())) {
+                continue;
+            }
+            seen.push((name.to_string(), location.to_string()));
+
+            let required = param.get("required").and_then(|r| r.as_bool()) == Some(true);
+            let mut field = self.field(
+                name,
+                param.get("schema").unwrap_or(&YmlValue::Null),
+                required && location != "path",
+                0,
+            );
+            if let (Some(description), Some(f)) = (
+                param.get("description").filter(|d| d.is_string()),
+                field.as_mapping_mut(),
+            ) {
+                f.insert(key("description"), description.clone());
+            }
+
+            match location {
+                "path" => path.push(field),
+                "query" => params.push(field),
+                "header" => headers.push(field),
+                _ => {} // cookies can not be declared
+            }
+        }
+
+        let mut allowlist = mapping([
+            ("path", YmlValue::Sequence(path)),
+            ("params", YmlValue::Sequence(params)),
+            ("headers", YmlValue::Sequence(headers)),
+        ]);
+        if let Some(schema) = operation
+            .get("requestBody")
+            .and_then(|b| self.media(b))
+            .and_then(|m| m.get("schema").cloned())
+        {
+            allowlist.insert(key("body"), YmlValue::Mapping(self.dsl_type(&schema, 0)));
+        }
+
+        allowlist
+    }
+
+    fn responses(&self, operation: &YmlValue) -> YmlMapping {
+        operation
+            .get("responses")
+            .and_then(|r| r.as_mapping())
+            .iter()
+            .flat_map(|r| r.iter())
+            .flat_map(|(status, response)| {
+                let status = match status {
+                    YmlValue::Number(n) => n.to_string(),
+                    other => other.as_str()?.to_string(),
+                };
+                let response = self.resolve(response, 0)?;
+
+                let mut declared = YmlMapping::new();
+                if let Some(description) = response.get("description").filter(|d| d.is_string()) {
+                    declared.insert(key("description"), description.clone());
+                }
+                if let Some(schema) = self.media(&response).and_then(|m| m.get("schema").cloned()) {
+                    declared.insert(key("body"), YmlValue::Mapping(self.dsl_type(&schema, 0)));
+                }
+                Some((key(&status), YmlValue::Mapping(declared)))
+            })
+            .collect()
+    }
+
+    /// status and example of the first successful response
+    fn mocked_response(&self, operation: &YmlValue) -> (u16, YmlValue) {
+        let success = operation
+            .get("responses")
+            .and_then(|r| r.as_mapping())
+            .iter()
+            .flat_map(|r| r.iter())
+            .flat_map(|(status, response)| {
+                let status: u16 = match status {
+                    YmlValue::Number(n) => n.as_u64()?.try_into().ok()?,
+                    other => other.as_str()?.parse().ok()?,
+                };
+                Some((status, response))
+            })
+            .filter(|(status, _)| (200..300).contains(status))
+            .min_by_key(|(status, _)| *status);
+
+        let Some((status, response)) = success else {
+            return (200, YmlValue::Null);
+        };
+        let example = self
+            .media(response)
+            .map(|m| self.media_example(&m))
+            .unwrap_or(YmlValue::Null);
+
+        (status, example)
+    }
+
+    fn endpoint(&self, shared_params: &[YmlValue], operation: &YmlValue) -> YmlValue {
+        let mut declaration = mapping([("call", key("declare"))]);
+        if let Some(description) = ["summary", "description"]
+            .iter()
+            .flat_map(|k| operation.get(k).filter(|d| d.is_string()))
+            .next()
+        {
+            declaration.insert(key("description"), description.clone());
+        }
+        declaration.insert(
+            key("allowlist"),
+            YmlValue::Mapping(self.allowlist(shared_params, operation)),
+        );
+        let responses = self.responses(operation);
+        if !responses.is_empty() {
+            declaration.insert(key("responses"), YmlValue::Mapping(responses));
+        }
+
+        let (status, example) = self.mocked_response(operation);
+        YmlValue::Mapping(mapping([
+            ("declaration", YmlValue::Mapping(declaration)),
+            (
+                "mock",
+                YmlValue::Mapping(mapping([
+                    ("call", key("reflect.mock")),
+                    ("args", example),
+                    ("result", key("example")),
+                ])),
+            ),
+            (
+                "response",
+                YmlValue::Mapping(mapping([
+                    ("return", key("${example}")),
+                    ("status", YmlValue::Number(status.into())),
+                    // generated endpoints return bodies exactly as the contract describes
+                    ("wrapper", YmlValue::Bool(false)),
+                ])),
+            ),
+        ]))
+    }
+}
+
+/// one endpoint file per operation of OpenAPI 3 document
+pub fn generate_skeletons(spec: &YmlValue) -> Result<Vec<SkeletonFile>, String> {
+    let version = spec.get("openapi").and_then(|v| v.as_str()).unwrap_or("");
+    if !version.starts_with('3') {
+        return Err("only OpenAPI 3 documents are supported".to_string());
+    }
+    let paths = spec
+        .get("paths")
+        .and_then(|p| p.as_mapping())
+        .ok_or("document has no paths")?;
+
+    let generator = Generator { spec };
+    let mut files = Vec::new();
+    for (url, item) in paths {
+        let (Some(url), Some(item)) = (url.as_str(), generator.resolve(item, 0)) else {
+            continue;
+        };
+        let shared_params = item
+            .get("parameters")
+            .and_then(|p| p.as_sequence())
+            .cloned()
+            .unwrap_or_default();
+
+        for (operation_key, method_dir) in METHODS {
+            let Some(operation) = item.get(operation_key) else {
+                continue;
+            };
+            let Some(path) = file_path(method_dir, url) else {
+                warn!("{} {} can not be mapped to DSL file", method_dir, url);
+                continue;
+            };
+
+            let endpoint = generator.endpoint(&shared_params, operation);
+            let content = serde_yaml_ng::to_string(&endpoint).map_err(|e| e.to_string())?;
+            files.push(SkeletonFile {
+                path,
+                content: format!("# {} {}\n{}", method_dir, url, content),
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, write};
+    use std::path::PathBuf;
+
+    use serde_json::json;
+    use serde_yaml_ng::Value as YmlValue;
+
+    use crate::endpoints::parser::EndpointsCollection;
+    use crate::endpoints::scaffold::{file_path, generate_skeletons};
+    use crate::endpoints::types::Request;
+    use crate::engine::{Engine, EngineConfig};
+
+    const SPEC: &str = r#"
+        openapi: 3.0.3
+        info:
+          title: users
+          version: 1.0.0
+        paths:
+          /users:
+            post:
+              summary: create user
+              requestBody:
+                content:
+                  application/json:
+                    schema:
+                      $ref: '#/components/schemas/User'
+              responses:
+                "201":
+                  description: created
+                  content:
+                    application/json:
+                      schema:
+                        $ref: '#/components/schemas/User'
+          /users/{id}:
+            parameters:
+              - name: id
+                in: path
+                required: true
+                schema:
+                  type: integer
+            get:
+              parameters:
+                - name: fields
+                  in: query
+                  required: true
+                  schema:
+                    type: string
+                    enum: [short, full]
+                - name: X-Tenant
+                  in: header
+                  schema:
+                    type: string
+              responses:
+                "200":
+                  description: user
+                  content:
+                    application/json:
+                      schema:
+                        $ref: '#/components/schemas/User'
+                      example:
+                        id: 7
+                        name: Ann
+                "404":
+                  description: not found
+        components:
+          schemas:
+            User:
+              type: object
+              required: [name]
+              properties:
+                id:
+                  type: integer
+                name:
+                  type: string
+                tags:
+                  type: array
+                  items:
+                    type: string
+    "#;
+
+    fn parse(content: &str) -> YmlValue {
+        serde_yaml_ng::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_file_path() {
+        assert_eq!(
+            file_path("GET", "/users/{id}/orders"),
+            Some(PathBuf::from("GET/users/[id]/orders.yml"))
+        );
+        assert_eq!(
+            file_path("POST", "/users"),
+            Some(PathBuf::from("POST/users.yml"))
+        );
+        assert_eq!(file_path("GET", "/"), None);
+        assert_eq!(file_path("GET", "/../etc"), None);
+    }
+
+    #[test]
+    fn test_generate_skeletons() {
+        let files = generate_skeletons(&parse(SPEC)).unwrap();
+        assert_eq!(files.len(), 2);
+
+        let post = files
+            .iter()
+            .find(|f| f.path.to_str() == Some("POST/users.yml"))
+            .unwrap();
+        let post = parse(&post.content);
+        let declaration = post.get("declaration").unwrap();
+        assert_eq!(declaration.get("description").unwrap(), "create user");
+        let body = declaration.get("allowlist").unwrap().get("body").unwrap();
+        assert_eq!(body.get("type").unwrap(), "object");
+        let fields = body.get("fields").unwrap().as_sequence().unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[1].get("field").unwrap(), "name");
+        assert_eq!(fields[1].get("required").unwrap(), &YmlValue::Bool(true));
+        assert_eq!(
+            fields[2].get("items").unwrap().get("type").unwrap(),
+            "string"
+        );
+        assert_eq!(
+            post.get("mock").unwrap().get("args").unwrap(),
+            &parse("{id: 0, name: string, tags: [string]}")
+        );
+        assert_eq!(post.get("response").unwrap().get("status").unwrap(), 201);
+
+        let get = files
+            .iter()
+            .find(|f| f.path.to_str() == Some("GET/users/[id].yml"))
+            .unwrap();
+        assert!(get.content.starts_with("# GET /users/{id}\n"));
+        let get = parse(&get.content);
+        let allowlist = get.get("declaration").unwrap().get("allowlist").unwrap();
+        assert_eq!(
+            allowlist.get("path").unwrap()[0].get("type").unwrap(),
+            "integer"
+        );
+        assert_eq!(
+            allowlist.get("params").unwrap()[0],
+            parse("{field: fields, type: string, enum: [short, full], required: true}")
+        );
+        assert_eq!(
+            allowlist.get("headers").unwrap()[0].get("field").unwrap(),
+            "X-Tenant"
+        );
+        let responses = get.get("declaration").unwrap().get("responses").unwrap();
+        assert_eq!(
+            responses.get("404").unwrap().get("description").unwrap(),
+            "not found"
+        );
+
+        assert!(generate_skeletons(&parse("swagger: '2.0'")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_skeletons_are_served() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for file in generate_skeletons(&parse(SPEC)).unwrap() {
+            let path = dir.join(&file.path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, file.content).unwrap();
+        }
+
+        let collection = EndpointsCollection::parse_from_dir(dir.to_str().unwrap());
+        assert_eq!(collection.endpoints.len(), 2);
+        let endpoint = collection
+            .endpoints
+            .iter()
+            .find(|e| e.url_path == "/users/{id}")
+            .unwrap();
+
+        let engine = Engine::from_endpoint(endpoint, &EngineConfig::new("./unittest_dsl"));
+        let res = engine.execute(Request::default()).await;
+        assert_eq!(res.1, 200);
+        assert_eq!(res.0, json!({"id": 7, "name": "Ann"}));
+    }
+}
@@ -0,0 +1,247 @@
+use std::cmp::Reverse;
+use std::fs::{Metadata, read_dir};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+};
+use log::warn;
+use percent_encoding::percent_decode_str;
+use tower::ServiceExt;
+use tower_http::services::ServeDir;
+
+/// `STATIC/` dir content is served under url of its parent dir.
+/// Guards are not applied to static files
+#[derive(Debug, Clone)]
+pub struct StaticDir {
+    pub url_prefix: String,
+    pub dir: PathBuf,
+}
+
+pub fn find_static_dirs(dsl_dir: &str) -> Vec<StaticDir> {
+    let mut static_dirs = Vec::new();
+    find_static_dirs_rec("", &PathBuf::from(dsl_dir), &mut static_dirs);
+
+    // the most specific prefix wins
+    static_dirs.sort_by_key(|d| Reverse(d.url_prefix.len()));
+    static_dirs
+}
+
+fn find_static_dirs_rec(current_url: &str, current_dir: &PathBuf, acc: &mut Vec<StaticDir>) {
+    read_dir(current_dir)
+        .ok()
+        .iter_mut()
+        .flat_map(|r| r.into_iter())
+        .flat_map(|e| e.ok())
+        .for_each(|f| {
+            let f_path = f.path();
+            if !f_path.is_dir() {
+                return;
+            }
+
+            let Some(name) = f.file_name().to_str().map(|n| n.to_string()) else {
+                return;
+            };
+
+            if name == "STATIC" {
+                acc.push(StaticDir {
+                    url_prefix: current_url.to_string(),
+                    dir: f_path,
+                });
+                return;
+            }
+
+            find_static_dirs_rec(&format!("{}/{}", current_url, name), &f_path, acc);
+        });
+}
+
+/// prefix has to match whole url segments, remaining path always starts with `/`
+fn strip_url_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(prefix)?;
+    if rest.is_empty() {
+        return Some("/");
+    }
+    if !rest.starts_with('/') {
+        return None;
+    }
+
+    Some(rest)
+}
+
+fn file_path(dir: &Path, url_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(url_path).decode_utf8().ok()?;
+    let relative = PathBuf::from(decoded.trim_start_matches('/'));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let path = dir.join(relative);
+    if path.is_dir() {
+        return Some(path.join("index.html"));
+    }
+
+    Some(path)
+}
+
+fn etag(metadata: &Metadata) -> Option<String> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!(
+        "W/\"{:x}-{:x}\"",
+        metadata.len(),
+        modified.as_nanos()
+    ))
+}
+
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || weak(tag) == weak(etag))
+}
+
+pub async fn serve_static(static_dirs: Arc<Vec<StaticDir>>, request: Request) -> Response {
+    let path = request.uri().path().to_string();
+    let Some((static_dir, rest)) = static_dirs
+        .iter()
+        .find_map(|d| Some((d, strip_url_prefix(&path, &d.url_prefix)?)))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let path_and_query = match parts.uri.query() {
+        Some(query) => format!("{}?{}", rest, query),
+        None => rest.to_string(),
+    };
+    match Uri::try_from(path_and_query) {
+        Ok(uri) => parts.uri = uri,
+        Err(e) => {
+            warn!("cannot rewrite static uri {}: {}", path, e);
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    }
+
+    let etag = match file_path(&static_dir.dir, rest) {
+        Some(file) => tokio::fs::metadata(file).await.ok().and_then(|m| etag(&m)),
+        None => None,
+    };
+
+    if let Some(etag) = &etag {
+        let if_none_match = parts
+            .headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|v| v.to_str().ok());
+        if if_none_match.is_some_and(|v| etag_matches(v, etag)) {
+            return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag.clone())]).into_response();
+        }
+    }
+
+    let request = Request::from_parts(parts, body);
+    let Ok(response) = ServeDir::new(&static_dir.dir).oneshot(request).await;
+    let mut response = response.into_response();
+
+    if let Some(etag) = etag
+        && response.status().is_success()
+        && let Ok(value) = HeaderValue::try_from(etag)
+    {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use axum::{
+        body::{Body, to_bytes},
+        http::{Request, header},
+    };
+
+    use crate::endpoints::statics::{
+        etag_matches, find_static_dirs, serve_static, strip_url_prefix,
+    };
+
+    #[test]
+    fn test_find_static_dirs() {
+        let dirs = find_static_dirs("./unittest_dsl");
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].url_prefix, "/test");
+    }
+
+    #[test]
+    fn test_strip_url_prefix() {
+        assert_eq!(strip_url_prefix("/some/a.json", "/some"), Some("/a.json"));
+        assert_eq!(strip_url_prefix("/some", "/some"), Some("/"));
+        assert_eq!(strip_url_prefix("/something", "/some"), None);
+        assert_eq!(strip_url_prefix("/a.json", ""), Some("/a.json"));
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("W/\"1-2\"", "W/\"1-2\""));
+        assert!(etag_matches("\"0\", \"1-2\"", "W/\"1-2\""));
+        assert!(etag_matches("*", "W/\"1-2\""));
+        assert!(!etag_matches("W/\"1-3\"", "W/\"1-2\""));
+    }
+
+    #[tokio::test]
+    async fn test_serve_static() {
+        let dirs = Arc::new(find_static_dirs("./unittest_dsl"));
+
+        let req = Request::builder()
+            .uri("/test/fixture.json")
+            .body(Body::empty())
+            .unwrap();
+        let resp = serve_static(dirs.clone(), req).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert!(resp.headers().contains_key(header::LAST_MODIFIED));
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "{\"fixture\": true}\n");
+
+        let req = Request::builder()
+            .uri("/test/fixture.json")
+            .header(header::IF_NONE_MATCH, etag)
+            .body(Body::empty())
+            .unwrap();
+        let resp = serve_static(dirs.clone(), req).await;
+        assert_eq!(resp.status().as_u16(), 304);
+
+        let req = Request::builder()
+            .uri("/test/fixture.json")
+            .header(header::RANGE, "bytes=1-9")
+            .body(Body::empty())
+            .unwrap();
+        let resp = serve_static(dirs.clone(), req).await;
+        assert_eq!(resp.status().as_u16(), 206);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "\"fixture\"");
+
+        let req = Request::builder()
+            .uri("/test/missing.json")
+            .body(Body::empty())
+            .unwrap();
+        let resp = serve_static(dirs.clone(), req).await;
+        assert_eq!(resp.status().as_u16(), 404);
+
+        let req = Request::builder()
+            .uri("/other/fixture.json")
+            .body(Body::empty())
+            .unwrap();
+        let resp = serve_static(dirs, req).await;
+        assert_eq!(resp.status().as_u16(), 404);
+    }
+}
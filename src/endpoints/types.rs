@@ -1,20 +1,41 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
 
-use axum::extract::{FromRequest, Json, Request as AxumRequest};
+use axum::body::Bytes;
+use axum::extract::{
+    ConnectInfo, FromRequest, FromRequestParts, MatchedPath, Path, Request as AxumRequest,
+};
+use axum::http::{HeaderMap, header};
+use percent_encoding::percent_decode_str;
 use rquickjs::{Ctx as JsCtx, IntoJs, Result as JsResult, Value as JsValue};
 use serde::Serialize;
 use serde_json::Value as JsonValue;
 
+use crate::access_log::REQUEST_ID_HEADER;
+use crate::endpoints::body::{parse_body, raw_body_to_json};
+
 #[derive(Default, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Request {
+    method: String,
+    route: String,
+    raw_path: String,
+    raw_query: String,
+    path: HashMap<String, String>,
     params: HashMap<String, String>,
+    params_all: HashMap<String, Vec<String>>,
     headers: HashMap<String, String>,
+    headers_all: HashMap<String, Vec<String>>,
+    cookies: HashMap<String, String>,
+    remote_addr: Option<String>,
+    remote_ip: Option<String>,
+    request_id: Option<String>,
     body: JsonValue,
+    raw_body: JsonValue,
 }
 
 impl<'js> IntoJs<'js> for Request {
     fn into_js(self, ctx: &JsCtx<'js>) -> JsResult<JsValue<'js>> {
-        // TODO implement path parsing?
         rquickjs_serde::to_value(ctx.clone(), self).map_err(|e| rquickjs::Error::IntoJs {
             from: "Request",
             to: "Value",
@@ -25,30 +46,95 @@ impl<'js> IntoJs<'js> for Request {
 
 impl Request {
     pub async fn from_request(r: AxumRequest) -> Self {
-        let headers = r
-            .headers()
+        let (mut parts, body) = r.into_parts();
+        let path = Path::<HashMap<String, String>>::from_request_parts(&mut parts, &())
+            .await
+            .map(|p| p.0)
+            .unwrap_or_default();
+        let route = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|m| m.as_str().to_string())
+            .unwrap_or_default();
+        let remote_addr = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|c| c.0);
+
+        let headers_all = Self::collect_headers(&parts.headers);
+        let headers = headers_all
             .iter()
-            .map(|(k, v)| {
-                (
-                    k.as_str().to_string(),
-                    v.to_str().unwrap_or("default").to_string(),
-                )
-            })
+            .flat_map(|(k, v)| Some((k.clone(), v.last()?.clone())))
             .collect();
+        let cookies = Self::parse_cookies(&parts.headers);
 
-        let query_params_str = r.uri().query().unwrap_or("");
-        let query_params = serde_urlencoded::from_str(query_params_str).unwrap_or_default();
+        let raw_query = parts.uri.query().unwrap_or("").to_string();
+        let query_pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(&raw_query).unwrap_or_default();
+        let mut params_all: HashMap<String, Vec<String>> = HashMap::new();
+        for (k, v) in &query_pairs {
+            params_all.entry(k.clone()).or_default().push(v.clone());
+        }
+        // single-valued view keeps the last occurrence of repeated key
+        let params = query_pairs.into_iter().collect();
 
-        let js_val = Json::from_request(r, &())
-            .await
-            .map(|js: Json<_>| js.0)
-            .unwrap_or(JsonValue::Null);
+        let method = parts.method.to_string();
+        let raw_path = parts.uri.path().to_string();
+        let request_headers = parts.headers.clone();
+        let request_id = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        let r = AxumRequest::from_parts(parts, body);
+        let bytes = Bytes::from_request(r, &()).await.unwrap_or_default();
+        let body = parse_body(&request_headers, &bytes).await;
 
         Self {
-            params: query_params,
-            headers: headers,
-            body: js_val,
+            method,
+            route,
+            raw_path,
+            raw_query,
+            path,
+            params,
+            params_all,
+            headers,
+            headers_all,
+            cookies,
+            remote_addr: remote_addr.map(|a| a.to_string()),
+            remote_ip: remote_addr.map(|a| a.ip().to_string()),
+            request_id,
+            body,
+            raw_body: raw_body_to_json(&bytes),
+        }
+    }
+
+    fn collect_headers(headers: &HeaderMap) -> HashMap<String, Vec<String>> {
+        let mut collected: HashMap<String, Vec<String>> = HashMap::new();
+        for (k, v) in headers {
+            collected
+                .entry(k.as_str().to_string())
+                .or_default()
+                .push(String::from_utf8_lossy(v.as_bytes()).to_string());
         }
+        collected
+    }
+
+    /// values are percent-decoded, `return` encodes the cookies it sets
+    fn parse_cookies(headers: &HeaderMap) -> HashMap<String, String> {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .flat_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .flat_map(|pair| pair.split_once('='))
+            .map(|(k, v)| {
+                let value = percent_decode_str(v.trim().trim_matches('"')).decode_utf8_lossy();
+                (k.trim().to_string(), value.to_string())
+            })
+            .filter(|(k, _)| !k.is_empty())
+            .collect()
     }
 
     pub fn new(
@@ -60,16 +146,125 @@ impl Request {
             params: query,
             headers: headers,
             body: body,
+            ..Default::default()
+        }
+    }
+
+    /// route data is filled by axum for incoming requests
+    pub fn with_route(
+        mut self,
+        method: &str,
+        route: &str,
+        raw_path: &str,
+        path: HashMap<String, String>,
+    ) -> Self {
+        self.method = method.to_string();
+        self.route = route.to_string();
+        self.raw_path = raw_path.to_string();
+        self.path = path;
+        self
+    }
+
+    /// requests made by templates keep id of the caller
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn route(&self) -> &str {
+        &self.route
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn path_params(&self) -> &HashMap<String, String> {
+        &self.path
+    }
+
+    pub fn params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    /// every value of repeated param, requests built by hand have `params` only
+    pub fn param_values(&self, name: &str) -> Vec<&str> {
+        match self.params_all.get(name) {
+            Some(values) => values.iter().map(|v| v.as_str()).collect(),
+            None => self
+                .params
+                .get(name)
+                .map(|v| v.as_str())
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// every value of repeated header, name is lowercase
+    pub fn header_values(&self, name: &str) -> Vec<&str> {
+        match self.headers_all.get(name) {
+            Some(values) => values.iter().map(|v| v.as_str()).collect(),
+            None => self
+                .headers
+                .get(name)
+                .map(|v| v.as_str())
+                .into_iter()
+                .collect(),
         }
     }
+
+    /// raw query is rebuilt without the param, so flows cannot read it from there
+    pub fn remove_param(&mut self, name: &str) {
+        self.params.remove(name);
+        self.params_all.remove(name);
+
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(&self.raw_query).unwrap_or_default();
+        let kept: Vec<(String, String)> = pairs.into_iter().filter(|(k, _)| k != name).collect();
+        self.raw_query = serde_urlencoded::to_string(kept).unwrap_or_default();
+    }
+
+    /// raw json body is replaced with the changed body,
+    /// raw bodies of other formats cannot be rebuilt and are dropped
+    pub fn sync_raw_body(&mut self) {
+        let is_json = self
+            .raw_body
+            .as_str()
+            .is_some_and(|r| serde_json::from_str::<JsonValue>(r).is_ok());
+        self.raw_body = match is_json {
+            true => JsonValue::String(self.body.to_string()),
+            false => JsonValue::Null,
+        };
+    }
+
+    pub fn body_mut(&mut self) -> &mut JsonValue {
+        &mut self.body
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::endpoints::types::Request;
-    use axum::{body::Body, extract::Request as AxumRequest};
+    use crate::engine::{Engine, EngineConfig};
+    use axum::{
+        Json, Router,
+        body::{Body, to_bytes},
+        extract::{ConnectInfo, Request as AxumRequest},
+        http::HeaderValue,
+        routing::get,
+    };
     use serde_json::json;
     use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
 
     #[test]
     fn test_request() {
@@ -110,13 +305,165 @@ mod test {
         let query = request.params;
         assert_eq!(query.get("aa").unwrap(), "bb");
         assert_eq!(query.get("cc").unwrap(), "dd");
+
+        assert_eq!(request.raw_body, json!({"test": "test"}).to_string());
+    }
+
+    #[tokio::test]
+    async fn test_request_full_from_axum() {
+        let req = AxumRequest::builder()
+            .method("PUT")
+            .uri("http://localhost:8090/test/path?a=1&b=2&a=3")
+            .header("x-multi", "one")
+            .header("x-multi", "two")
+            .header("x-binary", HeaderValue::from_bytes(&[0x61, 0xff]).unwrap())
+            .header("cookie", "session=abc; theme=\"dark\"")
+            .header("cookie", "other=1")
+            .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 5555))))
+            .body(Body::empty())
+            .unwrap();
+
+        let request = Request::from_request(req).await;
+
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.raw_path, "/test/path");
+        assert_eq!(request.raw_query, "a=1&b=2&a=3");
+
+        assert_eq!(request.params.get("a").unwrap(), "3");
+        assert_eq!(request.params_all.get("a").unwrap(), &vec!["1", "3"]);
+        assert_eq!(request.params_all.get("b").unwrap(), &vec!["2"]);
+
+        assert_eq!(request.headers.get("x-multi").unwrap(), "two");
+        assert_eq!(
+            request.headers_all.get("x-multi").unwrap(),
+            &vec!["one", "two"]
+        );
+        assert_eq!(request.headers.get("x-binary").unwrap(), "a\u{FFFD}");
+
+        assert_eq!(request.cookies.get("session").unwrap(), "abc");
+        assert_eq!(request.cookies.get("theme").unwrap(), "dark");
+        assert_eq!(request.cookies.get("other").unwrap(), "1");
+
+        assert_eq!(request.remote_addr.unwrap(), "10.0.0.1:5555");
+        assert_eq!(request.remote_ip.unwrap(), "10.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_cookie_round_trip() {
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    ret:
+                      return: ok
+                      cookies:
+                        session: ${"a b; c=d, é%"}
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+        let res = engine.execute(Request::default()).await;
+        let (_, set_cookie) = res.2.iter().find(|(k, _)| k == "set-cookie").unwrap();
+        let pair = set_cookie.split(';').next().unwrap();
+
+        let req = AxumRequest::builder()
+            .uri("/")
+            .header("cookie", pair)
+            .body(Body::empty())
+            .unwrap();
+        let request = Request::from_request(req).await;
+        assert_eq!(request.cookies.get("session").unwrap(), "a b; c=d, é%");
+    }
+
+    #[tokio::test]
+    async fn test_request_route_from_axum() {
+        let router = Router::new().route(
+            "/users/{id}",
+            get(|r: AxumRequest| async move {
+                let request = Request::from_request(r).await;
+                request.route
+            }),
+        );
+
+        let req = AxumRequest::builder()
+            .uri("/users/12")
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = router.oneshot(req).await.unwrap();
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+
+        assert_eq!(bytes, "/users/{id}");
+    }
+
+    #[tokio::test]
+    async fn test_request_form_from_axum() {
+        let req = AxumRequest::builder()
+            .uri("http://localhost:8090/test")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(Body::from("a=b&c=d"))
+            .unwrap();
+
+        let request = Request::from_request(req).await;
+
+        assert_eq!(request.body, json!({"a": "b", "c": "d"}));
+        assert_eq!(request.raw_body, "a=b&c=d");
+    }
+
+    #[tokio::test]
+    async fn test_request_path_from_axum() {
+        let router = Router::new().route(
+            "/users/{id}/orders/{order}",
+            get(|r: AxumRequest| async move {
+                let request = Request::from_request(r).await;
+                Json(request.path)
+            }),
+        );
+
+        let req = AxumRequest::builder()
+            .uri("/users/12/orders/abc")
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = router.oneshot(req).await.unwrap();
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let path: HashMap<String, String> = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(path.get("id").unwrap(), "12");
+        assert_eq!(path.get("order").unwrap(), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_request_catch_all_path_from_axum() {
+        let router = Router::new().route(
+            "/proxy/{*rest}",
+            get(|r: AxumRequest| async move {
+                let request = Request::from_request(r).await;
+                Json(request.path)
+            }),
+        );
+
+        let req = AxumRequest::builder()
+            .uri("/proxy/a/b/c")
+            .body(Body::empty())
+            .unwrap();
+
+        let resp = router.oneshot(req).await.unwrap();
+        let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let path: HashMap<String, String> = serde_json::from_slice(&bytes).unwrap();
+
+        assert_eq!(path.get("rest").unwrap(), "a/b/c");
     }
 
     #[test]
     fn test_default() {
         let req = Request::default();
+        assert!(req.path.is_empty());
         assert!(req.params.is_empty());
         assert!(req.headers.is_empty());
         assert!(req.body.is_null());
+        assert!(req.raw_body.is_null());
+        assert!(req.cookies.is_empty());
+        assert!(req.remote_addr.is_none());
     }
 }
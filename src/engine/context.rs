@@ -3,29 +3,101 @@ use rquickjs::{
     AsyncContext as AsynJsContext, AsyncRuntime as AsyncJsRuntime, IntoJs, Result as JsResult,
     Value as JsValue,
 };
-use serde_json::{Value as JsonValue, json};
+use serde_json::Value as JsonValue;
 use std::sync::RwLock;
+use tokio::sync::mpsc::Sender;
 
 use crate::endpoints::types::Request;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    Text,
+    Base64,
+}
+
 #[derive(Debug, Clone)]
 pub struct ReturnValue {
     pub json: JsonValue,
     pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub wrapper: Option<bool>,
+    pub format: BodyFormat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: JsonValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SocketMessage {
+    Send(JsonValue),
+    Close(Option<u16>, String),
+}
+
+/// DSL string parsed once when the flow is compiled
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// string without expressions, returned as is
+    Literal(String),
+    /// js source of `${}` expression or template string
+    Js(String),
+}
+
+impl Expr {
+    pub fn parse(expr: &str) -> Self {
+        // code of `wrap_js_code` runs as is, values it assigns may hold `${`
+        if let Some(code) = expr.strip_prefix("${").and_then(|e| e.strip_suffix("!}")) {
+            return Self::Js(format!("{}!", code));
+        }
+
+        if let Some(inner) = expr.strip_prefix("${").and_then(|e| e.strip_suffix('}'))
+            && !inner.contains("${")
+        {
+            return Self::Js(inner.to_string());
+        }
+
+        if expr.contains("${") && expr.contains('}') {
+            return Self::Js(format!("`{}`", expr));
+        }
+
+        Self::Literal(expr.to_string())
+    }
 }
 
+/// events or socket messages a slow client may be behind, flow waits when it is full
+pub const OUTGOING_BUFFER: usize = 64;
+
 pub struct Context {
     pub context: Option<AsynJsContext>,
+    pub events: Option<Sender<StreamEvent>>,
+    pub socket: Option<Sender<SocketMessage>>,
+    /// forwarded to upstream services by http tasks
+    pub request_id: Option<String>,
     pub status_code: RwLock<u16>,
     pub return_json: RwLock<JsonValue>,
+    pub return_headers: RwLock<Vec<(String, String)>>,
+    pub return_wrapper: RwLock<Option<bool>>,
+    pub return_format: RwLock<BodyFormat>,
 }
 
 impl Context {
     pub async fn from_request(request: Request, dsl_path: &str) -> Self {
+        let request_id = request.request_id().map(|r| r.to_string());
         let context = Self::get_context(request).await.ok();
         let ctx = Self {
             status_code: RwLock::new(200),
             return_json: RwLock::new(JsonValue::Null),
+            return_headers: RwLock::new(Vec::new()),
+            return_wrapper: RwLock::new(None),
+            return_format: RwLock::new(BodyFormat::Json),
+            events: None,
+            socket: None,
+            request_id,
             context: context,
         };
 
@@ -61,6 +133,13 @@ impl Context {
                 .map(|r| r.clone())
                 .unwrap_or(JsonValue::Null),
             status: self.status_code.read().map(|r| r.clone()).unwrap_or(500),
+            headers: self
+                .return_headers
+                .read()
+                .map(|r| r.clone())
+                .unwrap_or_default(),
+            wrapper: self.return_wrapper.read().map(|r| *r).unwrap_or(None),
+            format: self.return_format.read().map(|r| *r).unwrap_or_default(),
         }
     }
 
@@ -86,38 +165,73 @@ impl Context {
         }
     }
 
+    pub async fn evaluate(&self, expr: &Expr) -> JsonValue {
+        match expr {
+            Expr::Literal(s) => JsonValue::String(s.clone()),
+            Expr::Js(source) => self.execute_js_signle_line(source).await,
+        }
+    }
+
     pub async fn evaluate_expr(&self, expr: &str) -> JsonValue {
-        let expr_copy = expr.to_string();
+        self.evaluate(&Expr::parse(expr)).await
+    }
 
-        if self.is_obj(&expr_copy) {
-            return self
-                .execute_js_signle_line(&expr_copy[2..expr_copy.len() - 1])
-                .await;
-        }
+    pub fn set_return_value(&mut self, status_code: u16, value: JsonValue) {
+        self.return_json.get_mut().map(|r| *r = value).ok();
+        self.status_code.get_mut().map(|r| *r = status_code).ok();
+    }
 
-        if self.is_template_string(expr) {
-            return self
-                .execute_js_signle_line(&format!("`{}`", expr_copy))
-                .await;
-        }
+    pub fn set_return_headers(&mut self, headers: Vec<(String, String)>) {
+        self.return_headers.get_mut().map(|r| *r = headers).ok();
+    }
 
-        json!(expr_copy)
+    pub fn set_return_format(&mut self, wrapper: Option<bool>, format: BodyFormat) {
+        self.return_wrapper.get_mut().map(|r| *r = wrapper).ok();
+        self.return_format.get_mut().map(|r| *r = format).ok();
     }
 
-    fn is_obj(&self, expr: &str) -> bool {
-        if !(expr.starts_with("${") && expr.ends_with("}")) {
-            return false;
-        }
-        !expr[2..expr.len() - 1].contains("${")
+    pub fn set_events_sender(&mut self, sender: Sender<StreamEvent>) {
+        self.events = Some(sender);
     }
 
-    fn is_template_string(&self, expr: &str) -> bool {
-        expr.contains("${") && expr.contains("}")
+    /// returns false when the streaming client is gone,
+    /// waits while the client is behind by `OUTGOING_BUFFER` events
+    pub async fn emit(&self, event: StreamEvent) -> bool {
+        let Some(sender) = &self.events else {
+            warn!(
+                "Event emitted outside of streaming endpoint, skipping: {:?}",
+                event
+            );
+            return true;
+        };
+
+        sender.send(event).await.is_ok()
     }
 
-    pub fn set_return_value(&mut self, status_code: u16, value: JsonValue) {
-        self.return_json.get_mut().map(|r| *r = value).ok();
-        self.status_code.get_mut().map(|r| *r = status_code).ok();
+    pub fn set_socket_sender(&mut self, sender: Sender<SocketMessage>) {
+        self.socket = Some(sender);
+    }
+
+    /// returns false when the websocket connection is gone,
+    /// waits while the client is behind by `OUTGOING_BUFFER` messages
+    pub async fn send_socket(&self, message: SocketMessage) -> bool {
+        let Some(sender) = &self.socket else {
+            warn!(
+                "Socket message sent outside of websocket endpoint, skipping: {:?}",
+                message
+            );
+            return true;
+        };
+
+        sender.send(message).await.is_ok()
+    }
+
+    pub async fn set_variable(&self, name: &str, value: &JsonValue) {
+        self.evaluate_expr(&Context::wrap_js_code(&format!(
+            "var {} = {};",
+            name, value
+        )))
+        .await;
     }
 
     pub fn wrap_js_code(code: &str) -> String {
@@ -127,9 +241,13 @@ impl Context {
 
 #[cfg(test)]
 mod test {
-    use crate::{endpoints::types::Request, engine::context::Context};
+    use crate::{
+        endpoints::types::Request,
+        engine::context::{BodyFormat, Context, Expr, SocketMessage, StreamEvent},
+    };
     use serde_json::json;
     use std::collections::HashMap;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_context() {
@@ -154,6 +272,18 @@ mod test {
         let res = context.get_return_value();
         assert_eq!(res.json, json!({"hello": "world"}));
         assert_eq!(res.status, 201);
+        assert!(res.headers.is_empty());
+
+        context.set_return_headers(vec![("x-test".to_string(), "1".to_string())]);
+        let res = context.get_return_value();
+        assert_eq!(res.headers, vec![("x-test".to_string(), "1".to_string())]);
+        assert_eq!(res.wrapper, None);
+        assert_eq!(res.format, BodyFormat::Json);
+
+        context.set_return_format(Some(false), BodyFormat::Text);
+        let res = context.get_return_value();
+        assert_eq!(res.wrapper, Some(false));
+        assert_eq!(res.format, BodyFormat::Text);
 
         context
             .evaluate_expr(&Context::wrap_js_code("let someVar = 33;"))
@@ -166,4 +296,69 @@ mod test {
 
         drop(context);
     }
+
+    #[test]
+    fn test_parse_expr() {
+        assert_eq!(Expr::parse("${a + 1}"), Expr::Js("a + 1".to_string()));
+        assert_eq!(Expr::parse("id ${a}"), Expr::Js("`id ${a}`".to_string()));
+        assert_eq!(
+            Expr::parse("${a}-${b}"),
+            Expr::Js("`${a}-${b}`".to_string())
+        );
+        assert_eq!(Expr::parse("plain"), Expr::Literal("plain".to_string()));
+        assert_eq!(
+            Expr::parse(&Context::wrap_js_code(r#"var a = "${b}";"#)),
+            Expr::Js(r#"var a = "${b}";!"#.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emit() {
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let event = StreamEvent {
+            event: Some("progress".to_string()),
+            id: None,
+            data: json!({"done": 1}),
+        };
+
+        assert!(context.emit(event.clone()).await);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        context.set_events_sender(tx);
+        assert!(context.emit(event.clone()).await);
+
+        // flow waits for the client to take the previous event
+        let blocked = tokio::time::timeout(Duration::from_millis(50), context.emit(event.clone()));
+        assert!(blocked.await.is_err());
+        assert_eq!(rx.recv().await.unwrap(), event);
+
+        drop(rx);
+        assert!(!context.emit(event).await);
+    }
+
+    #[tokio::test]
+    async fn test_socket() {
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let message = SocketMessage::Send(json!("hello"));
+
+        assert!(context.send_socket(message.clone()).await);
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        context.set_socket_sender(tx);
+        assert!(context.send_socket(message.clone()).await);
+
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(50),
+            context.send_socket(message.clone()),
+        );
+        assert!(blocked.await.is_err());
+        assert_eq!(rx.recv().await.unwrap(), message);
+
+        drop(rx);
+        assert!(!context.send_socket(message).await);
+
+        context.set_variable("message", &json!({"a": 1})).await;
+        let res = context.evaluate_expr("${message.a}").await;
+        assert_eq!(res, 1);
+    }
 }
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_yaml_ng::Value as YmlValue;
+
+use crate::args::types::GraphFormat;
+use crate::endpoints::parser::Endpoint;
+use crate::engine::tasks::produce_task;
+use crate::engine::tasks::task::{next_task_name, preprocess_obj};
+
+const START: &str = "start";
+// `end` is a keyword in Mermaid
+const END: &str = "stop";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Terminal,
+    Guard,
+    Task,
+    Switch,
+    File,
+    Missing,
+}
+
+#[derive(Debug)]
+struct Node {
+    id: String,
+    lines: Vec<String>,
+    shape: Shape,
+}
+
+#[derive(Debug)]
+struct Edge {
+    from: String,
+    to: String,
+    label: Option<String>,
+    dashed: bool,
+}
+
+/// tasks of an endpoint flow with the order they run in,
+/// guards run before the flow, templates are linked to their files
+#[derive(Debug, Default)]
+pub struct FlowGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+impl FlowGraph {
+    pub fn from_endpoint(endpoint: &Endpoint, dsl_path: &str) -> Self {
+        let mut graph = Self::default();
+        graph.add_node(START, vec![START.to_string()], Shape::Terminal);
+
+        let mut previous = START.to_string();
+        for (i, guard) in endpoint.guards.iter().enumerate() {
+            let id = format!("g{}", i);
+            let lines = vec!["guard".to_string(), relative(&guard.source, dsl_path)];
+            graph.add_node(&id, lines, Shape::Guard);
+            graph.add_edge(&previous, &id, None, false);
+            previous = id;
+        }
+
+        // edge into the flow goes before edges of the flow
+        let at = graph.edges.len();
+        let entry = graph.add_flow(&endpoint.yml_content);
+        graph.add_edge(&previous, entry.as_deref().unwrap_or(END), None, false);
+        let edge = graph.edges.remove(graph.edges.len() - 1);
+        graph.edges.insert(at, edge);
+        graph.add_node(END, vec!["end".to_string()], Shape::Terminal);
+
+        graph
+    }
+
+    fn add_node(&mut self, id: &str, lines: Vec<String>, shape: Shape) {
+        self.nodes.push(Node {
+            id: id.to_string(),
+            lines,
+            shape,
+        });
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, label: Option<String>, dashed: bool) {
+        self.edges.push(Edge {
+            from: from.to_string(),
+            to: to.to_string(),
+            label,
+            dashed,
+        });
+    }
+
+    /// adds tasks of the flow, returns id of the task the flow starts with
+    fn add_flow(&mut self, yml: &YmlValue) -> Option<String> {
+        let yml = preprocess_obj(yml);
+        let mapping = yml.as_mapping()?;
+
+        let ids: HashMap<&str, String> = mapping
+            .keys()
+            .flat_map(|k| k.as_str())
+            .enumerate()
+            .map(|(i, name)| (name, format!("t{}", i)))
+            .collect();
+
+        let mut entry = None;
+        for (key, task) in mapping {
+            let Some(name) = key.as_str() else {
+                continue;
+            };
+            let id = &ids[name];
+
+            // unknown tasks are skipped by the engine
+            let Some(kind) = produce_task(name, &yml).map(|t| t.get_type()) else {
+                self.add_node(
+                    id,
+                    vec!["unknown".to_string(), name.to_string()],
+                    Shape::Task,
+                );
+                continue;
+            };
+            entry.get_or_insert(id.clone());
+
+            let branches: Vec<(&str, &str)> = task
+                .get("switch")
+                .and_then(|s| s.as_sequence())
+                .into_iter()
+                .flatten()
+                .flat_map(|c| Some((c.get("condition")?.as_str()?, c.get("next")?.as_str()?)))
+                .collect();
+            let shape = match kind {
+                "switch" => Shape::Switch,
+                _ => Shape::Task,
+            };
+            self.add_node(id, vec![kind.to_string(), name.to_string()], shape);
+
+            for (condition, next) in &branches {
+                let to = self.target(&ids, next);
+                self.add_edge(id, &to, Some(condition.to_string()), false);
+            }
+
+            let next = next_task_name(name, &yml);
+            let to = match &next {
+                Some(next) => self.target(&ids, next),
+                None => END.to_string(),
+            };
+            let label = (!branches.is_empty()).then(|| "else".to_string());
+            self.add_edge(id, &to, label, false);
+
+            if let Some(template) = task.get("template").and_then(|t| t.as_str()) {
+                let file = format!("f{}", self.nodes.len());
+                self.add_node(&file, vec![template.to_string()], Shape::File);
+                self.add_edge(id, &file, None, true);
+            }
+        }
+
+        entry
+    }
+
+    /// id of the next task, missing tasks get a node of their own
+    fn target(&mut self, ids: &HashMap<&str, String>, next: &str) -> String {
+        if next == "end" {
+            return END.to_string();
+        }
+        if let Some(id) = ids.get(next) {
+            return id.clone();
+        }
+
+        let id = format!("m{}", self.nodes.len());
+        let lines = vec!["missing".to_string(), next.to_string()];
+        self.add_node(&id, lines, Shape::Missing);
+        id
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Mermaid => self.to_mermaid(),
+            GraphFormat::Dot => self.to_dot(),
+        }
+    }
+
+    fn to_mermaid(&self) -> String {
+        let escape = |s: &str| s.replace('"', "#quot;");
+        let mut out = vec!["flowchart TD".to_string()];
+
+        for node in &self.nodes {
+            let label = node
+                .lines
+                .iter()
+                .map(|l| escape(l))
+                .collect::<Vec<_>>()
+                .join("<br/>");
+            let (open, close) = match node.shape {
+                Shape::Terminal => ("((", "))"),
+                Shape::Guard => ("[[", "]]"),
+                Shape::Task => ("[", "]"),
+                Shape::Switch => ("{", "}"),
+                Shape::File => ("[/", "/]"),
+                Shape::Missing => ("[(", ")]"),
+            };
+            out.push(format!("    {}{}\"{}\"{}", node.id, open, label, close));
+        }
+
+        for edge in &self.edges {
+            let arrow = if edge.dashed { "-.->" } else { "-->" };
+            let label = match &edge.label {
+                Some(l) => format!("|\"{}\"|", escape(l)),
+                None => "".to_string(),
+            };
+            out.push(format!("    {} {}{} {}", edge.from, arrow, label, edge.to));
+        }
+
+        out.join("\n")
+    }
+
+    fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut out = vec![
+            "digraph flow {".to_string(),
+            "    node [shape=box];".to_string(),
+        ];
+
+        for node in &self.nodes {
+            let label = node
+                .lines
+                .iter()
+                .map(|l| escape(l))
+                .collect::<Vec<_>>()
+                .join("\\n");
+            let attrs = match node.shape {
+                Shape::Terminal => ", shape=circle".to_string(),
+                Shape::Guard => ", shape=box, peripheries=2".to_string(),
+                Shape::Task => "".to_string(),
+                Shape::Switch => ", shape=diamond".to_string(),
+                Shape::File => format!(", shape=note, URL=\"{}\"", label),
+                Shape::Missing => ", style=dashed, color=red".to_string(),
+            };
+            out.push(format!("    {} [label=\"{}\"{}];", node.id, label, attrs));
+        }
+
+        for edge in &self.edges {
+            let mut attrs = vec![];
+            if let Some(label) = &edge.label {
+                attrs.push(format!("label=\"{}\"", escape(label)));
+            }
+            if edge.dashed {
+                attrs.push("style=dashed".to_string());
+            }
+            let attrs = if attrs.is_empty() {
+                "".to_string()
+            } else {
+                format!(" [{}]", attrs.join(", "))
+            };
+            out.push(format!("    {} -> {}{};", edge.from, edge.to, attrs));
+        }
+
+        out.push("}".to_string());
+        out.join("\n")
+    }
+}
+
+/// guard files are shown relative to DSL dir
+fn relative(source: &str, dsl_path: &str) -> String {
+    Path::new(source)
+        .strip_prefix(dsl_path)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| source.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::args::types::GraphFormat;
+    use crate::endpoints::parser::EndpointsCollection;
+    use crate::engine::graph::FlowGraph;
+
+    fn graph(yml: &str) -> FlowGraph {
+        let mut graph = FlowGraph::default();
+        graph.add_flow(&serde_yaml_ng::from_str(yml).unwrap());
+        graph
+    }
+
+    #[test]
+    fn test_flow_edges() {
+        let graph = graph(
+            r#"
+                check:
+                  switch:
+                    - condition: ${incoming.params.id == "1"}
+                      next: one
+                    - condition: ${false}
+                      next: nowhere
+                prepare:
+                  assign:
+                    x: 1
+                one:
+                  template: TEMPLATES/user.yml
+                  next: end
+            "#,
+        );
+
+        let edges: Vec<(&str, &str, Option<&str>, bool)> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.label.as_deref(), e.dashed))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("t0", "t2", Some("${incoming.params.id == \"1\"}"), false),
+                ("t0", "m1", Some("${false}"), false),
+                ("t0", "t1", Some("else"), false),
+                ("t1", "t2", None, false),
+                ("t2", "stop", None, false),
+                ("t2", "f4", None, true),
+            ]
+        );
+
+        let mermaid = graph.render(GraphFormat::Mermaid);
+        assert!(mermaid.contains(r#"t0{"switch<br/>check"}"#), "{}", mermaid);
+        assert!(mermaid.contains(r#"t0 -->|"${incoming.params.id == #quot;1#quot;}"| t2"#));
+        assert!(mermaid.contains(r#"m1[("missing<br/>nowhere")]"#));
+        assert!(mermaid.contains(r#"t2 -.-> f4"#));
+
+        let dot = graph.render(GraphFormat::Dot);
+        assert!(dot.contains(r#"t1 [label="assign\nprepare"];"#), "{}", dot);
+        assert!(dot.contains(r#"t0 -> t1 [label="else"];"#));
+        assert!(dot.contains(r#"f4 [label="TEMPLATES/user.yml", shape=note"#));
+    }
+
+    #[test]
+    fn test_endpoint_graph() {
+        let collection = EndpointsCollection::parse_from_dir("./unittest_dsl");
+        let (endpoint, _) = collection.find("GET", "/test/test/endp").unwrap();
+
+        let mermaid =
+            FlowGraph::from_endpoint(endpoint, "./unittest_dsl").render(GraphFormat::Mermaid);
+        assert!(
+            mermaid.starts_with("flowchart TD\n    start((\"start\"))"),
+            "{}",
+            mermaid
+        );
+        assert!(mermaid.contains(r#"g0[["guard<br/>"#));
+        assert!(mermaid.contains("start --> g0\n    g0 --> g1\n    g1 --> t0\n    t0 --> stop"));
+    }
+}
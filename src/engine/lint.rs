@@ -0,0 +1,428 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use itertools::Itertools;
+use log::{error, warn};
+use serde_yaml_ng::Value as YmlValue;
+
+use crate::endpoints::parser::{Endpoint, EndpointsCollection};
+use crate::endpoints::reload::bad_yml_files;
+use crate::engine::tasks::produce_task;
+use crate::engine::tasks::task::{next_task_name, preprocess_obj};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// problem found in DSL dir, flows run with warnings but may behave unexpectedly
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    pub level: Level,
+    pub file: String,
+    pub task: Option<String>,
+    pub message: String,
+}
+
+impl LintIssue {
+    fn error(file: &str, task: Option<&str>, message: String) -> Self {
+        Self {
+            level: Level::Error,
+            file: file.to_string(),
+            task: task.map(|t| t.to_string()),
+            message,
+        }
+    }
+
+    fn warning(file: &str, task: Option<&str>, message: String) -> Self {
+        Self {
+            level: Level::Warning,
+            ..Self::error(file, task, message)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Error
+    }
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        match &self.task {
+            Some(task) => write!(
+                f,
+                "{}: {}: task `{}`: {}",
+                level, self.file, task, self.message
+            ),
+            None => write!(f, "{}: {}: {}", level, self.file, self.message),
+        }
+    }
+}
+
+/// `next` of the task and of its switch branches, `end` is not a task
+fn explicit_refs(task: &YmlValue) -> Vec<&str> {
+    let switch_branches = task
+        .get("switch")
+        .and_then(|s| s.as_sequence())
+        .into_iter()
+        .flatten()
+        .flat_map(|c| c.get("next")?.as_str());
+
+    task.get("next")
+        .and_then(|n| n.as_str())
+        .into_iter()
+        .chain(switch_branches)
+        .filter(|n| *n != "end")
+        .collect()
+}
+
+/// task names the flow may continue with after the task
+fn successors(task_name: &str, yml: &YmlValue) -> Vec<String> {
+    let switch_branches = yml
+        .get(task_name)
+        .into_iter()
+        .flat_map(explicit_refs)
+        .map(|n| n.to_string());
+
+    next_task_name(task_name, yml)
+        .into_iter()
+        .chain(switch_branches)
+        .collect()
+}
+
+fn lint_switch(file: &str, task_name: &str, task: &YmlValue, issues: &mut Vec<LintIssue>) {
+    let Some(conditions) = task.get("switch").and_then(|s| s.as_sequence()) else {
+        return;
+    };
+
+    for c in conditions {
+        let (Some(condition), Some(_)) = (
+            c.get("condition").and_then(|c| c.as_str()),
+            c.get("next").and_then(|n| n.as_str()),
+        ) else {
+            issues.push(LintIssue::error(
+                file,
+                Some(task_name),
+                "switch entry needs `condition` and `next`".to_string(),
+            ));
+            continue;
+        };
+
+        if !(condition.starts_with("${") && condition.ends_with("}")) {
+            issues.push(LintIssue::error(
+                file,
+                Some(task_name),
+                format!(
+                    "condition \"{}\" is not wrapped in ${{}} and is always false",
+                    condition
+                ),
+            ));
+        }
+    }
+}
+
+/// template paths are relative to DSL dir, rendered paths cannot be checked
+fn template_path(task: &YmlValue, dsl_path: &str) -> Option<PathBuf> {
+    let template = task.get("template")?.as_str()?;
+    if template.contains("${") {
+        return None;
+    }
+    Some(PathBuf::from(dsl_path).join(template))
+}
+
+/// checks tasks of one flow, returns template files the flow uses
+fn lint_flow(
+    file: &str,
+    yml: &YmlValue,
+    dsl_path: &str,
+    issues: &mut Vec<LintIssue>,
+) -> Vec<PathBuf> {
+    let yml = preprocess_obj(yml);
+    let Some(mapping) = yml.as_mapping() else {
+        if !yml.is_null() {
+            issues.push(LintIssue::error(
+                file,
+                None,
+                "flow has to be a mapping of tasks".to_string(),
+            ));
+        }
+        return vec![];
+    };
+
+    let mut known = HashSet::new();
+    let mut entry = None;
+    let mut templates = vec![];
+    for (key, task) in mapping {
+        let Some(name) = key.as_str() else {
+            issues.push(LintIssue::error(
+                file,
+                None,
+                format!("task name {:?} is not a string", key),
+            ));
+            continue;
+        };
+
+        if produce_task(name, &yml).is_none() {
+            issues.push(LintIssue::error(
+                file,
+                Some(name),
+                "unknown task shape".to_string(),
+            ));
+            continue;
+        }
+        known.insert(name);
+        entry.get_or_insert(name);
+
+        lint_switch(file, name, task, issues);
+
+        if let Some(path) = template_path(task, dsl_path) {
+            if path.is_file() {
+                templates.push(path);
+            } else {
+                issues.push(LintIssue::error(
+                    file,
+                    Some(name),
+                    format!("template file {} does not exist", path.display()),
+                ));
+            }
+        }
+    }
+
+    // unknown shapes are reported above, dangling references point to no task at all
+    for (key, task) in mapping {
+        let Some(name) = key.as_str().filter(|n| known.contains(n)) else {
+            continue;
+        };
+        for next in explicit_refs(task) {
+            if !mapping.contains_key(next) {
+                issues.push(LintIssue::error(
+                    file,
+                    Some(name),
+                    format!("`next` refers to missing task `{}`", next),
+                ));
+            }
+        }
+    }
+
+    let mut reachable = HashSet::new();
+    let mut queue: VecDeque<String> = entry.map(|e| e.to_string()).into_iter().collect();
+    while let Some(name) = queue.pop_front() {
+        if !known.contains(name.as_str()) || !reachable.insert(name.clone()) {
+            continue;
+        }
+        queue.extend(successors(&name, &yml));
+    }
+
+    for key in mapping.keys() {
+        if let Some(name) = key.as_str()
+            && known.contains(name)
+            && !reachable.contains(name)
+        {
+            issues.push(LintIssue::warning(
+                file,
+                Some(name),
+                "task is unreachable".to_string(),
+            ));
+        }
+    }
+
+    templates
+}
+
+/// endpoints the router cannot serve together, compared by route shape regardless of method:
+/// captures named differently at the same position and duplicates like `[id].yml` and `{id}.yml`
+fn lint_routes(collection: &EndpointsCollection, issues: &mut Vec<LintIssue>) {
+    let mut routes: Vec<&Endpoint> = vec![];
+    for endpoint in collection
+        .endpoints
+        .iter()
+        .sorted_by(|l, r| l.source.cmp(&r.source))
+    {
+        if let Some(conflict) = routes.iter().find_map(|r| endpoint.conflict_with(r)) {
+            issues.push(LintIssue::error(&endpoint.source, None, conflict));
+            continue;
+        }
+        routes.push(endpoint);
+    }
+}
+
+/// checks every endpoint, guard and used template of DSL dir
+pub fn lint_dsl(dsl_path: &str) -> Vec<LintIssue> {
+    let mut issues: Vec<LintIssue> = bad_yml_files(&PathBuf::from(dsl_path))
+        .into_iter()
+        .map(|(path, e)| LintIssue::error(&path.display().to_string(), None, e))
+        .collect();
+
+    // conflicting endpoints are reported, not skipped
+    let collection = EndpointsCollection::parse_all_from_dir(dsl_path);
+    lint_routes(&collection, &mut issues);
+
+    let mut checked = HashSet::new();
+    let mut templates = vec![];
+    for endpoint in &collection.endpoints {
+        let guards = endpoint.guards.iter().map(|g| (&g.source, &g.yml_content));
+        let flows = guards.chain([(&endpoint.source, &endpoint.yml_content)]);
+        for (source, yml) in flows {
+            if checked.insert(PathBuf::from(source)) {
+                templates.extend(lint_flow(source, yml, dsl_path, &mut issues));
+            }
+        }
+    }
+
+    // templates are flows too and can include other templates
+    while let Some(path) = templates.pop() {
+        if !checked.insert(path.clone()) {
+            continue;
+        }
+        let Some(yml) = read_to_string(&path)
+            .ok()
+            .and_then(|c| serde_yaml_ng::from_str::<YmlValue>(&c).ok())
+        else {
+            continue; // reported as bad yml file
+        };
+        let source = path.display().to_string();
+        templates.extend(lint_flow(&source, &yml, dsl_path, &mut issues));
+    }
+
+    issues.sort_by(|l, r| l.file.cmp(&r.file));
+    issues
+}
+
+/// strict mode refuses DSL dir with errors, issues are logged
+pub fn strict_check(dsl_path: &str) -> Result<(), String> {
+    let issues = lint_dsl(dsl_path);
+    for issue in &issues {
+        match issue.level {
+            Level::Error => error!("{}", issue),
+            Level::Warning => warn!("{}", issue),
+        }
+    }
+
+    match issues.iter().filter(|i| i.is_error()).count() {
+        0 => Ok(()),
+        n => Err(format!("DSL validation failed with {} errors", n)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::lint::{Level, LintIssue, lint_dsl, lint_flow};
+
+    fn lint(yml: &str) -> Vec<LintIssue> {
+        let mut issues = vec![];
+        lint_flow(
+            "test.yml",
+            &serde_yaml_ng::from_str(yml).unwrap(),
+            "./unittest_dsl",
+            &mut issues,
+        );
+        issues
+    }
+
+    fn messages(issues: &[LintIssue]) -> Vec<(Option<&str>, &str)> {
+        issues
+            .iter()
+            .map(|i| (i.task.as_deref(), i.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_flow() {
+        let issues = lint(
+            r#"
+                check:
+                  switch:
+                    - condition: ${incoming.params.id == "1"}
+                      next: one
+                  next: other
+                one:
+                  return: one
+                  next: end
+                other:
+                  return: other
+            "#,
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_broken_flow() {
+        let issues = lint(
+            r#"
+                check:
+                  switch:
+                    - condition: "true"
+                      next: one
+                    - condition: ${true}
+                      next: missing
+                  next: end
+                weird:
+                  some: task
+                one:
+                  template: TEMPLATES/not_exists.yml
+                  next: nowhere
+                orphan:
+                  return: never
+            "#,
+        );
+
+        assert_eq!(
+            messages(&issues),
+            vec![
+                (
+                    Some("check"),
+                    "condition \"true\" is not wrapped in ${} and is always false"
+                ),
+                (Some("weird"), "unknown task shape"),
+                (
+                    Some("one"),
+                    "template file ./unittest_dsl/TEMPLATES/not_exists.yml does not exist"
+                ),
+                (Some("check"), "`next` refers to missing task `missing`"),
+                (Some("one"), "`next` refers to missing task `nowhere`"),
+                (Some("orphan"), "task is unreachable"),
+            ]
+        );
+        assert_eq!(issues.last().unwrap().level, Level::Warning);
+        assert_eq!(
+            issues[1].to_string(),
+            "error: test.yml: task `weird`: unknown task shape"
+        );
+    }
+
+    #[test]
+    fn test_lint_dsl() {
+        assert!(lint_dsl("./unittest_dsl").iter().all(|i| !i.is_error()));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for file in [
+            "GET/users/[id].yml",
+            "GET/users/{id}.yml",
+            "PUT/users/[userId].yml",
+            "DELETE/users/[id]/posts.yml",
+        ] {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), "ret:\n  return: ok\n").unwrap();
+        }
+        std::fs::write(dir.join("GET/bad.yml"), "a: [").unwrap();
+
+        let issues = lint_dsl(dir.to_str().unwrap());
+        assert_eq!(issues.len(), 3, "{:?}", issues);
+        assert!(issues[0].file.ends_with("bad.yml"));
+        assert!(issues[1].file.ends_with("GET/users/{id}.yml"));
+        assert!(issues[1].message.contains("is already defined by"));
+        assert!(issues[2].file.ends_with("PUT/users/[userId].yml"));
+        assert!(
+            issues[2]
+                .message
+                .starts_with("capture {userId} of /users/{userId} conflicts with {id}")
+        );
+    }
+}
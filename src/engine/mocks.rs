@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use axum::http::{Response as HttpResponse, header};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+tokio::task_local! {
+    static UPSTREAM_MOCKS: Arc<UpstreamMocks>;
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// canned response for outbound `http` task calls
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamMock {
+    /// any method if not set
+    pub method: Option<String>,
+    pub url: String,
+    /// rendered `query` of the task has to contain these params
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: JsonValue,
+}
+
+impl UpstreamMock {
+    fn matches(&self, method: &str, url: &str, query: &HashMap<String, String>) -> bool {
+        self.method
+            .as_ref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(method))
+            && self.url == url
+            && self.query.iter().all(|(k, v)| query.get(k) == Some(v))
+    }
+
+    /// body is sent as json unless mock declares another content type
+    fn to_response(&self) -> Option<reqwest::Response> {
+        let content_type = self
+            .headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(header::CONTENT_TYPE.as_str()))
+            .map(|(_, v)| v.as_str());
+
+        let body = match (&self.body, content_type) {
+            (JsonValue::String(s), Some(t)) if !t.starts_with("application/json") => s.clone(),
+            (body, _) => body.to_string(),
+        };
+
+        let mut builder = HttpResponse::builder().status(self.status);
+        if content_type.is_none() {
+            builder = builder.header(header::CONTENT_TYPE, "application/json");
+        }
+        for (k, v) in &self.headers {
+            builder = builder.header(k, v);
+        }
+
+        builder.body(body).ok().map(reqwest::Response::from)
+    }
+}
+
+/// mocks are checked in order, calls without a mock are collected and never sent
+#[derive(Debug, Default)]
+pub struct UpstreamMocks {
+    mocks: Vec<UpstreamMock>,
+    unmatched: Mutex<Vec<String>>,
+}
+
+impl UpstreamMocks {
+    pub fn new(mocks: Vec<UpstreamMock>) -> Self {
+        Self {
+            mocks,
+            unmatched: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn respond(
+        &self,
+        method: &str,
+        url: &str,
+        query: &HashMap<String, String>,
+    ) -> Option<reqwest::Response> {
+        let mock = self.mocks.iter().find(|m| m.matches(method, url, query));
+        if mock.is_none()
+            && let Ok(mut unmatched) = self.unmatched.lock()
+        {
+            unmatched.push(format!("{} {}", method, url));
+        }
+
+        mock?.to_response()
+    }
+
+    pub fn unmatched(&self) -> Vec<String> {
+        self.unmatched.lock().map(|u| u.clone()).unwrap_or_default()
+    }
+}
+
+pub fn current_mocks() -> Option<Arc<UpstreamMocks>> {
+    UPSTREAM_MOCKS.try_with(|m| m.clone()).ok()
+}
+
+/// `http` tasks run by the future get canned responses instead of calling upstreams
+pub async fn with_mocks<F: Future>(mocks: Option<Arc<UpstreamMocks>>, f: F) -> F::Output {
+    match mocks {
+        Some(mocks) => UPSTREAM_MOCKS.scope(mocks, f).await,
+        None => f.await,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::engine::mocks::{UpstreamMock, UpstreamMocks, current_mocks, with_mocks};
+
+    #[tokio::test]
+    async fn test_upstream_mocks() {
+        let mocks: Vec<UpstreamMock> = serde_yaml_ng::from_str(
+            r#"
+                - method: GET
+                  url: http://users/api
+                  query:
+                    id: "1"
+                  body:
+                    name: John
+                - url: http://text
+                  headers:
+                    content-type: text/plain
+                  body: hello
+            "#,
+        )
+        .unwrap();
+        let mocks = UpstreamMocks::new(mocks);
+
+        let query = HashMap::from([("id".to_string(), "1".to_string())]);
+        let response = mocks.respond("GET", "http://users/api", &query).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!({"name": "John"})
+        );
+
+        let response = mocks.respond("POST", "http://text", &HashMap::new());
+        assert_eq!(response.unwrap().text().await.unwrap(), "hello");
+
+        assert!(mocks.respond("POST", "http://users/api", &query).is_none());
+        assert!(
+            mocks
+                .respond("GET", "http://users/api", &HashMap::new())
+                .is_none()
+        );
+        assert_eq!(mocks.unmatched().len(), 2);
+
+        assert!(current_mocks().is_none());
+        let mocks = Arc::new(mocks);
+        let scoped = with_mocks(Some(mocks), async { current_mocks().is_some() }).await;
+        assert!(scoped);
+    }
+}
@@ -1,58 +1,272 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::{
+    http::{HeaderName, HeaderValue, StatusCode, header},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use log::warn;
 use serde_json::{Value as JsonValue, json};
 use serde_yaml_ng::Value as YmlValue;
+use tracing::{Instrument, info_span};
 
+use crate::args::types::{Args, RequestValidation};
 use crate::endpoints::parser::Endpoint;
 use crate::endpoints::types::Request;
-use crate::engine::context::Context;
+use crate::engine::context::{BodyFormat, Context, OUTGOING_BUFFER, ReturnValue, StreamEvent};
+use crate::engine::mocks::{current_mocks, with_mocks};
+use crate::engine::tasks::declaration::declared_timeout;
 use crate::engine::tasks::produce_task;
-use crate::engine::tasks::task::{Task, preprocess_obj};
+use crate::engine::tasks::task::{ExecutionResult, Task, preprocess_obj};
+use crate::engine::validation::{Allowlist, ResponseSchemas};
+use crate::limits::{DEFAULT_MAX_STEPS, MAX_TEMPLATE_DEPTH};
+use crate::metrics::METRICS;
+use crate::telemetry::request_span;
 
 mod context;
+pub mod graph;
+pub mod lint;
+pub mod mocks;
 mod tasks;
+mod validation;
+mod websocket;
 
+/// flow compiled once: tasks are kept at positions of their keys,
+/// so `next` of a task is an index and execution does no lookups by name
 #[derive(Debug)]
 struct TaskTree {
-    tasks: Vec<Box<dyn Task>>,
+    /// unknown tasks leave empty slots, flow stops when it gets to them
+    tasks: Vec<Option<Box<dyn Task>>>,
+    entry: Option<usize>,
 }
 
 impl TaskTree {
-    fn from_yml(yml: &YmlValue) -> Self {
+    fn from_yml(yml: &YmlValue, config: &EngineConfig) -> Self {
         let preprocessed_yml = preprocess_obj(yml);
         let Some(mapping) = preprocessed_yml.as_mapping() else {
-            return Self { tasks: vec![] };
+            return Self {
+                tasks: vec![],
+                entry: None,
+            };
         };
 
-        let tasks: Vec<Box<dyn Task>> = mapping
+        let mut tasks: Vec<Option<Box<dyn Task>>> = mapping
             .keys()
-            .flat_map(|k| Some(k.as_str()?))
-            .flat_map(|k| produce_task(k, &preprocessed_yml))
+            .map(|k| produce_task(k.as_str()?, &preprocessed_yml))
             .collect();
+        for task in tasks.iter_mut().flatten() {
+            task.prepare(config);
+        }
+        let entry = tasks.iter().position(|t| t.is_some());
+
+        Self { tasks, entry }
+    }
+
+    /// every executed task takes a step from the budget, so cyclic `next` chains are stopped
+    async fn walk_through(
+        &self,
+        mut context: Context,
+        budget: &Budget,
+    ) -> Result<Context, FlowAbort> {
+        let mut next = self.entry;
+        while let Some(task) = next.and_then(|i| self.tasks.get(i)?.as_deref()) {
+            budget.step(task.get_name())?;
+            let res = Self::execute_task(task, context).await;
+            context = res.0;
+            next = res.1;
+        }
+
+        Ok(context)
+    }
+
+    async fn execute_task(task: &dyn Task, context: Context) -> ExecutionResult {
+        let span = info_span!(
+            "task",
+            otel.name = format!("{} {}", task.get_type(), task.get_name()),
+            task.name = task.get_name(),
+            task.r#type = task.get_type(),
+        );
+
+        let start = Instant::now();
+        let res = task.execute(context).instrument(span).await;
+        METRICS.observe_task(task.get_type(), start.elapsed());
+        res
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.tasks.iter().flatten().any(|t| t.is_streaming())
+    }
+}
+
+/// flow is aborted when it runs too many tasks or for too long
+#[derive(Debug, Clone, PartialEq)]
+enum FlowAbort {
+    StepLimit { task: String, max_steps: usize },
+    Timeout(Duration),
+    TemplateDepth(usize),
+}
+
+impl FlowAbort {
+    fn to_json(&self) -> JsonValue {
+        match self {
+            FlowAbort::StepLimit { task, max_steps } => json!({
+                "error": format!("flow exceeded {} steps", max_steps),
+                "task": task,
+            }),
+            FlowAbort::Timeout(timeout) => json!({
+                "error": format!("flow timed out after {} ms", timeout.as_millis()),
+            }),
+            FlowAbort::TemplateDepth(max_depth) => json!({
+                "error": format!("templates nested deeper than {}", max_depth),
+            }),
+        }
+    }
 
-        Self { tasks: tasks }
+    fn to_response(&self) -> EngineResponse {
+        let status = match self {
+            FlowAbort::StepLimit { .. } | FlowAbort::TemplateDepth(_) => 500,
+            FlowAbort::Timeout(_) => 504,
+        };
+        EngineResponse(self.to_json(), status, vec![], BodyFormat::Json)
+    }
+}
+
+tokio::task_local! {
+    /// budget of the running request, templates executed by its flow take from it
+    static FLOW_BUDGET: Arc<Budget>;
+}
+
+/// limits shared by guards, flow and templates of one request
+#[derive(Debug)]
+struct Budget {
+    steps: Arc<AtomicUsize>,
+    max_steps: usize,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    /// templates the flow is nested in
+    depth: usize,
+}
+
+impl Budget {
+    fn new(max_steps: usize, timeout: Option<Duration>) -> Self {
+        Self {
+            steps: Arc::new(AtomicUsize::new(0)),
+            max_steps,
+            timeout,
+            deadline: timeout.map(|t| Instant::now() + t),
+            depth: 0,
+        }
+    }
+
+    /// template takes steps and time of its caller, every nested template grows the stack
+    fn nested(&self) -> Result<Self, FlowAbort> {
+        if self.depth >= MAX_TEMPLATE_DEPTH {
+            warn!(
+                "Flow aborted, templates nested deeper than {}",
+                MAX_TEMPLATE_DEPTH
+            );
+            return Err(FlowAbort::TemplateDepth(MAX_TEMPLATE_DEPTH));
+        }
+
+        Ok(Self {
+            steps: self.steps.clone(),
+            max_steps: self.max_steps,
+            timeout: self.timeout,
+            deadline: self.deadline,
+            depth: self.depth + 1,
+        })
+    }
+
+    /// deadline is checked here too, tasks which never yield cannot be timed out by tokio
+    fn step(&self, task: &str) -> Result<(), FlowAbort> {
+        let steps = self.steps.fetch_add(1, Ordering::Relaxed) + 1;
+        if steps > self.max_steps {
+            warn!(
+                "Flow aborted at task {} after {} steps",
+                task, self.max_steps
+            );
+            return Err(FlowAbort::StepLimit {
+                task: task.to_string(),
+                max_steps: self.max_steps,
+            });
+        }
+
+        if let (Some(timeout), Some(deadline)) = (self.timeout, self.deadline)
+            && Instant::now() >= deadline
+        {
+            warn!("Flow timed out at task {}", task);
+            return Err(FlowAbort::Timeout(timeout));
+        }
+        Ok(())
     }
 
-    async fn walk_through(&self, context: Context) -> Context {
-        let Some(task) = self.tasks.first() else {
-            return context;
+    /// limits time spent awaiting inside tasks, e.g. slow upstreams,
+    /// templates executed by `f` share this budget
+    async fn limit<T>(self: &Arc<Self>, f: impl Future<Output = T>) -> Result<T, FlowAbort> {
+        let (Some(timeout), Some(deadline)) = (self.timeout, self.deadline) else {
+            return Ok(FLOW_BUDGET.scope(self.clone(), f).await);
         };
 
-        let mut res = task.execute(context).await;
-        while let Some(next) = res.1 {
-            let mut next_task = None;
-            for task in &self.tasks {
-                if task.get_name() == next {
-                    next_task = Some(task);
-                }
+        let limited = tokio::time::timeout_at(deadline.into(), f);
+        match FLOW_BUDGET.scope(self.clone(), limited).await {
+            Ok(res) => Ok(res),
+            Err(_) => {
+                warn!("Flow timed out after {} ms", timeout.as_millis());
+                Err(FlowAbort::Timeout(timeout))
             }
+        }
+    }
+}
 
-            let Some(task) = next_task else {
-                break;
-            };
-            res = task.execute(res.0).await;
+/// streaming flow runs in its own task, which is cancelled when client goes away
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub dsl_path: String,
+    pub wrap_response: bool,
+    pub validation: RequestValidation,
+    pub dev_mode: bool,
+    /// tasks one request may execute, guards included
+    pub max_steps: usize,
+    /// used by endpoints without `timeout` in `declare`
+    pub timeout: Option<Duration>,
+}
+
+impl EngineConfig {
+    pub fn new(dsl_path: &str) -> Self {
+        Self {
+            dsl_path: dsl_path.to_string(),
+            wrap_response: true,
+            validation: RequestValidation::Off,
+            dev_mode: false,
+            max_steps: DEFAULT_MAX_STEPS,
+            timeout: None,
         }
+    }
 
-        return res.0;
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            dsl_path: args.dsl_path.clone(),
+            wrap_response: !args.no_wrapper,
+            validation: args.validate_requests,
+            dev_mode: args.dev_mode,
+            max_steps: args.max_steps,
+            timeout: args.flow_timeout,
+        }
     }
 }
 
@@ -60,81 +274,327 @@ impl TaskTree {
 pub struct Engine {
     guards: Vec<TaskTree>,
     tree: TaskTree,
-    dsl_path: String,
+    allowlist: Option<Allowlist>,
+    responses: Option<ResponseSchemas>,
+    timeout: Option<Duration>,
+    config: EngineConfig,
 }
 
-pub struct EngineResponse(pub JsonValue, pub u16);
+pub struct EngineResponse(
+    pub JsonValue,
+    pub u16,
+    pub Vec<(String, String)>,
+    pub BodyFormat,
+);
+
+impl EngineResponse {
+    fn body(&self) -> Option<(&'static str, Vec<u8>)> {
+        match self.3 {
+            BodyFormat::Json => Some(("application/json", self.0.to_string().into_bytes())),
+            BodyFormat::Text => {
+                let text = match &self.0 {
+                    JsonValue::String(s) => s.clone(),
+                    JsonValue::Null => "".to_string(),
+                    other => other.to_string(),
+                };
+                Some(("text/plain; charset=utf-8", text.into_bytes()))
+            }
+            BodyFormat::Base64 => {
+                let decoded = BASE64.decode(self.0.as_str().unwrap_or_default());
+                if let Err(e) = &decoded {
+                    warn!("cannot decode base64 response body: {}", e);
+                }
+                Some(("application/octet-stream", decoded.ok()?))
+            }
+        }
+    }
+}
+
+/// axum panics on line breaks in event name and id, such fields are dropped
+fn sse_field(field: &str, value: Option<String>) -> Option<String> {
+    let value = value?;
+    if value.contains(['\r', '\n', '\0']) {
+        warn!("Dropping event {} with line break: {:?}", field, value);
+        return None;
+    }
+    Some(value)
+}
+
+impl From<StreamEvent> for Event {
+    fn from(value: StreamEvent) -> Self {
+        let data = match value.data {
+            JsonValue::String(s) => s,
+            other => other.to_string(),
+        };
+
+        let mut event = Event::default();
+        if let Some(name) = sse_field("name", value.event) {
+            event = event.event(name);
+        }
+        if let Some(id) = sse_field("id", value.id) {
+            event = event.id(id);
+        }
+        // data is split into lines by axum, which panics on `\r`
+        event.data(data.replace("\r\n", "\n").replace('\r', "\n"))
+    }
+}
 
 impl IntoResponse for EngineResponse {
     fn into_response(self) -> axum::response::Response {
-        (
+        let Some((content_type, body)) = self.body() else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+
+        let mut response = (
             StatusCode::from_u16(self.1).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-            self.0.to_string(),
+            [(header::CONTENT_TYPE, content_type)],
+            body,
         )
-            .into_response()
+            .into_response();
+
+        let headers = response.headers_mut();
+        for (k, v) in self.2 {
+            let (Ok(name), Ok(value)) = (HeaderName::try_from(&k), HeaderValue::try_from(&v))
+            else {
+                warn!("Skipping invalid response header {}: {}", k, v);
+                continue;
+            };
+
+            if name == header::CONTENT_TYPE {
+                headers.insert(name, value);
+            } else {
+                headers.append(name, value);
+            }
+        }
+
+        response
     }
 }
 
 impl Engine {
-    pub fn from_endpoint(endpoint: &Endpoint, dsl_path: &str) -> Self {
+    pub fn from_endpoint(endpoint: &Endpoint, config: &EngineConfig) -> Self {
         Self {
             guards: endpoint
                 .guards
                 .iter()
-                .map(|g| TaskTree::from_yml(&g.yml_content))
+                .map(|g| TaskTree::from_yml(&g.yml_content, config))
                 .collect(),
-            tree: TaskTree::from_yml(&endpoint.yml_content),
-            dsl_path: dsl_path.to_string(),
+            tree: TaskTree::from_yml(&endpoint.yml_content, config),
+            allowlist: Allowlist::from_endpoint(endpoint),
+            responses: ResponseSchemas::from_endpoint(endpoint),
+            timeout: declared_timeout(&endpoint.yml_content).or(config.timeout),
+            config: config.clone(),
         }
     }
 
-    pub fn from_template(template: &YmlValue, dsl_path: &str) -> Self {
+    pub fn from_template(template: &YmlValue, config: &EngineConfig) -> Self {
         Self {
             guards: vec![],
-            tree: TaskTree::from_yml(&template),
-            dsl_path: dsl_path.to_string(),
+            tree: TaskTree::from_yml(template, config),
+            allowlist: None,
+            responses: None,
+            timeout: config.timeout,
+            config: config.clone(),
         }
     }
 
-    pub async fn execute(&self, request: Request) -> EngineResponse {
-        let mut context = Context::from_request(request, &self.dsl_path).await;
-        for guard in &self.guards {
-            context = guard.walk_through(context).await;
-            let return_value = context.get_return_value();
-            if return_value.status < 200 || return_value.status >= 300 {
-                return EngineResponse(
-                    json!({
-                        "response": return_value.json
-                    }),
-                    return_value.status,
-                );
-            }
+    /// dev mode only, mismatches are logged and response is returned as is
+    fn check_response(&self, return_value: &ReturnValue) {
+        let Some(responses) = &self.responses else {
+            return;
+        };
+        if !self.config.dev_mode || return_value.format != BodyFormat::Json {
+            return;
         }
 
-        context = self.tree.walk_through(context).await;
-        let return_value = context.get_return_value();
+        for violation in responses.check(return_value.status, &return_value.json) {
+            warn!(
+                "{} returned {} not matching declared response: {}: {}",
+                responses.endpoint, return_value.status, violation.field, violation.message
+            );
+        }
+    }
 
-        EngineResponse(
+    fn to_response(&self, return_value: ReturnValue) -> EngineResponse {
+        self.check_response(&return_value);
+        let wrap = return_value.format == BodyFormat::Json
+            && return_value.wrapper.unwrap_or(self.config.wrap_response);
+
+        let body = if wrap {
             json!({
                 "response": return_value.json,
-            }),
+            })
+        } else {
+            return_value.json
+        };
+
+        EngineResponse(
+            body,
             return_value.status,
+            return_value.headers,
+            return_value.format,
         )
     }
+
+    /// runs before guards, so guards and flow see only validated request
+    fn check_request(&self, mut request: Request) -> Result<Request, EngineResponse> {
+        let Some(allowlist) = &self.allowlist else {
+            return Ok(request);
+        };
+        let strip = match self.config.validation {
+            RequestValidation::Off => return Ok(request),
+            RequestValidation::Reject => false,
+            RequestValidation::Strip => true,
+        };
+
+        match allowlist.check(&mut request, strip) {
+            Ok(()) => Ok(request),
+            Err(violations) => Err(EngineResponse(
+                json!({
+                    "error": "invalid request",
+                    "violations": violations,
+                }),
+                400,
+                vec![],
+                BodyFormat::Json,
+            )),
+        }
+    }
+
+    fn budget(&self) -> Arc<Budget> {
+        Arc::new(Budget::new(self.config.max_steps, self.timeout))
+    }
+
+    async fn run_guards(
+        &self,
+        mut context: Context,
+        budget: &Budget,
+    ) -> Result<Context, EngineResponse> {
+        for guard in &self.guards {
+            context = guard
+                .walk_through(context, budget)
+                .await
+                .map_err(|abort| abort.to_response())?;
+            let return_value = context.get_return_value();
+            if return_value.status < 200 || return_value.status >= 300 {
+                return Err(self.to_response(return_value));
+            }
+        }
+
+        Ok(context)
+    }
+
+    /// flow stops at the next await when client disconnects and the future is dropped
+    pub async fn execute(&self, request: Request) -> EngineResponse {
+        let span = request_span(&request);
+        async move {
+            let request = match self.check_request(request) {
+                Ok(r) => r,
+                Err(response) => return response,
+            };
+
+            // templates run inside the flow of their caller and take from its budget
+            let budget = match FLOW_BUDGET.try_with(|b| b.nested()) {
+                Ok(Ok(nested)) => Arc::new(nested),
+                Ok(Err(abort)) => return abort.to_response(),
+                Err(_) => self.budget(),
+            };
+            let flow = async {
+                let context = Context::from_request(request, &self.config.dsl_path).await;
+                let context = self.run_guards(context, &budget).await?;
+                let context = self
+                    .tree
+                    .walk_through(context, &budget)
+                    .await
+                    .map_err(|abort| abort.to_response())?;
+                Ok::<_, EngineResponse>(self.to_response(context.get_return_value()))
+            };
+
+            match budget.limit(flow).await {
+                Ok(Ok(response) | Err(response)) => response,
+                Err(abort) => abort.to_response(),
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.tree.is_streaming()
+    }
+
+    /// guards are executed before the stream is opened, so they still can reject the request.
+    /// Value of the final `return` task (if any) is sent as the last `return` event,
+    /// aborted flow sends `error` event instead
+    pub async fn execute_stream(self: Arc<Self>, request: Request) -> Response {
+        let span = request_span(&request);
+        let budget = self.budget();
+        let guarded = async {
+            let request = self.check_request(request)?;
+            let context = Context::from_request(request, &self.config.dsl_path).await;
+            self.run_guards(context, &budget).await
+        };
+        let guarded = budget.limit(guarded);
+        let mut context = match guarded.instrument(span.clone()).await {
+            Ok(Ok(c)) => c,
+            Ok(Err(response)) => return response.into_response(),
+            Err(abort) => return abort.to_response().into_response(),
+        };
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(OUTGOING_BUFFER);
+        context.set_events_sender(sender.clone());
+
+        let mocks = current_mocks();
+        let handle = tokio::spawn(with_mocks(
+            mocks,
+            async move {
+                let flow = self.tree.walk_through(context, &budget);
+                let (event, data) = match budget.limit(flow).await.and_then(|r| r) {
+                    Ok(context) => ("return", context.get_return_value().json),
+                    Err(abort) => ("error", abort.to_json()),
+                };
+                if !data.is_null() {
+                    sender
+                        .send(StreamEvent {
+                            event: Some(event.to_string()),
+                            id: None,
+                            data,
+                        })
+                        .await
+                        .ok();
+                }
+            }
+            .instrument(span),
+        ));
+        let flow = AbortOnDrop(handle.abort_handle());
+
+        // flow is aborted when the stream is dropped on client disconnect
+        let stream = futures::stream::unfold((receiver, flow), |(mut receiver, flow)| async move {
+            let event = receiver.recv().await?;
+            Some((Ok::<Event, Infallible>(event.into()), (receiver, flow)))
+        });
+
+        Sse::new(stream)
+            .keep_alive(KeepAlive::default())
+            .into_response()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
+        args::types::RequestValidation,
         endpoints::{
             parser::{Endpoint, Guard},
             types::Request,
         },
-        engine::Engine,
+        engine::{Engine, EngineConfig, TaskTree},
     };
-    use axum::response::IntoResponse;
+    use axum::{body::to_bytes, http::header, response::IntoResponse};
     use serde_json::{Value as JsonValue, json};
     use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
     #[tokio::test]
     async fn test_from_template() {
@@ -146,7 +606,7 @@ mod test {
                 "#,
             )
             .unwrap(),
-            "./unittest_dsl",
+            &EngineConfig::new("./unittest_dsl"),
         );
 
         let res = engine.execute(Request::default()).await;
@@ -175,6 +635,7 @@ mod test {
                     "#,
                 )
                 .unwrap(),
+                source: "".into(),
             }],
             tag: "some".to_string(),
             url_path: "/some/".to_string(),
@@ -187,9 +648,11 @@ mod test {
             )
             .unwrap(),
             merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
         };
 
-        let engine = Engine::from_endpoint(&endpoint, "./unittest_dsl");
+        let engine = Engine::from_endpoint(&endpoint, &EngineConfig::new("./unittest_dsl"));
 
         let res = engine.execute(Request::default()).await;
 
@@ -211,4 +674,563 @@ mod test {
         let status = resp.status();
         assert_eq!(status.as_u16(), 400);
     }
+
+    #[tokio::test]
+    async fn test_response_headers() {
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    return:
+                      return: ok
+                      headers:
+                        cache-control: no-cache
+                      cookies:
+                        a: "1"
+                        b: "2"
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let resp = engine.execute(Request::default()).await.into_response();
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(headers.get(header::CACHE_CONTROL).unwrap(), "no-cache");
+        assert_eq!(
+            headers
+                .get_all(header::SET_COOKIE)
+                .iter()
+                .map(|v| v.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["a=1", "b=2"]
+        );
+
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    return:
+                      return: ok
+                      contentType: application/problem+json
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let resp = engine.execute(Request::default()).await.into_response();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream() {
+        let engine = Arc::new(Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    init:
+                      assign:
+                        x: 0
+
+                    inc:
+                      assign:
+                        x: ${x + 1}
+
+                    progress:
+                      emit:
+                        done: ${x}
+                      event: progress
+
+                    cond:
+                      switch:
+                        - condition: ${x < 3}
+                          next: inc
+
+                    result:
+                      return: finished
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        ));
+        assert!(engine.is_streaming());
+
+        let resp = engine.execute_stream(Request::default()).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            body,
+            concat!(
+                "event: progress\ndata: {\"done\":1}\n\n",
+                "event: progress\ndata: {\"done\":2}\n\n",
+                "event: progress\ndata: {\"done\":3}\n\n",
+                "event: return\ndata: finished\n\n",
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_line_breaks() {
+        let engine = Arc::new(Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    progress:
+                      emit: ${"one\r\ntwo\rthree"}
+                      event: ${"bad\nname"}
+                      id: ${"bad\rid"}
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        ));
+
+        let resp = engine.execute_stream(Request::default()).await;
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "data: one\ndata: two\ndata: three\n\n");
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_guard_rejects() {
+        let endpoint = Endpoint {
+            guards: vec![Guard {
+                yml_content: serde_yaml_ng::from_str(
+                    r#"
+                        reject:
+                          return: forbidden
+                          status: 403
+                    "#,
+                )
+                .unwrap(),
+                source: "".into(),
+            }],
+            tag: "some".to_string(),
+            url_path: "/some/".to_string(),
+            method: rstmytype::ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                    progress:
+                      emit: started
+                "#,
+            )
+            .unwrap(),
+            merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
+        };
+
+        let engine = Arc::new(Engine::from_endpoint(
+            &endpoint,
+            &EngineConfig::new("./unittest_dsl"),
+        ));
+        let resp = engine.execute_stream(Request::default()).await;
+        assert_eq!(resp.status().as_u16(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_request_validation() {
+        let endpoint = Endpoint {
+            guards: vec![],
+            tag: "some".to_string(),
+            url_path: "/some/".to_string(),
+            method: rstmytype::ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                    declaration:
+                      call: declare
+                      allowlist:
+                        params:
+                          - field: limit
+                            type: integer
+                            required: true
+
+                    test:
+                      return: ${incoming.params}
+                "#,
+            )
+            .unwrap(),
+            merged_declaration: r#"
+                declaration:
+                  call: declare
+                  allowlist:
+                    params:
+                      - field: limit
+                        type: integer
+                        required: true
+            "#
+            .into(),
+            websocket: false,
+            source: "".into(),
+        };
+        let params = HashMap::from([
+            ("limit".to_string(), "5".to_string()),
+            ("page".to_string(), "2".to_string()),
+        ]);
+
+        let engine = Engine::from_endpoint(&endpoint, &EngineConfig::new("./unittest_dsl"));
+        let res = engine
+            .execute(Request::new(
+                HashMap::new(),
+                JsonValue::Null,
+                params.clone(),
+            ))
+            .await;
+        assert_eq!(res.1, 200);
+
+        let mut config = EngineConfig::new("./unittest_dsl");
+        config.validation = RequestValidation::Reject;
+        let engine = Engine::from_endpoint(&endpoint, &config);
+        let res = engine
+            .execute(Request::new(
+                HashMap::new(),
+                JsonValue::Null,
+                params.clone(),
+            ))
+            .await;
+        assert_eq!(res.1, 400);
+        assert_eq!(
+            res.0,
+            json!({
+                "error": "invalid request",
+                "violations": [{"field": "params.page", "message": "field is not allowed"}],
+            })
+        );
+
+        config.validation = RequestValidation::Strip;
+        let engine = Engine::from_endpoint(&endpoint, &config);
+        let res = engine
+            .execute(Request::new(HashMap::new(), JsonValue::Null, params))
+            .await;
+        assert_eq!(res.1, 200);
+        assert_eq!(res.0, json!({"response": {"limit": "5"}}));
+
+        let res = engine.execute(Request::default()).await;
+        assert_eq!(res.1, 400);
+    }
+
+    #[tokio::test]
+    async fn test_response_wrapper() {
+        let template = serde_yaml_ng::from_str(
+            r#"
+                return:
+                  return:
+                    a: b
+                  wrapper: false
+            "#,
+        )
+        .unwrap();
+
+        let engine = Engine::from_template(&template, &EngineConfig::new("./unittest_dsl"));
+        let res = engine.execute(Request::default()).await;
+        assert_eq!(res.0, json!({"a": "b"}));
+
+        let template = serde_yaml_ng::from_str(
+            r#"
+                return:
+                  return:
+                    a: b
+            "#,
+        )
+        .unwrap();
+
+        let mut config = EngineConfig::new("./unittest_dsl");
+        config.wrap_response = false;
+        let engine = Engine::from_template(&template, &config);
+        let res = engine.execute(Request::default()).await;
+        assert_eq!(res.0, json!({"a": "b"}));
+
+        let template = serde_yaml_ng::from_str(
+            r#"
+                return:
+                  return:
+                    a: b
+                  wrapper: true
+            "#,
+        )
+        .unwrap();
+
+        let engine = Engine::from_template(&template, &config);
+        let res = engine.execute(Request::default()).await;
+        assert_eq!(res.0, json!({"response": {"a": "b"}}));
+    }
+
+    #[tokio::test]
+    async fn test_raw_response_body() {
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    return:
+                      return: <h1>${"hello"}</h1>
+                      format: text
+                      contentType: text/html
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let resp = engine.execute(Request::default()).await.into_response();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html"
+        );
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "<h1>hello</h1>");
+
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    return:
+                      return: aGVsbG8=
+                      format: base64
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let resp = engine.execute(Request::default()).await.into_response();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/octet-stream"
+        );
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "hello");
+
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(
+                r#"
+                    return:
+                      return: not base64!
+                      format: base64
+                "#,
+            )
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let resp = engine.execute(Request::default()).await.into_response();
+        assert_eq!(resp.status().as_u16(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_compiled_tree() {
+        let yml = serde_yaml_ng::from_str(
+            r#"
+                weird:
+                  some: task
+
+                check:
+                  switch:
+                    - condition: ${incoming.params.skip === "1"}
+                      next: weird
+                  next: result
+
+                unused:
+                  return: unused
+
+                result:
+                  return: ok
+            "#,
+        )
+        .unwrap();
+
+        let tree = TaskTree::from_yml(&yml, &EngineConfig::new("./unittest_dsl"));
+        assert_eq!(tree.entry, Some(1));
+        assert!(tree.tasks[0].is_none());
+        assert_eq!(tree.tasks.len(), 4);
+
+        let engine = Engine::from_template(&yml, &EngineConfig::new("./unittest_dsl"));
+        let res = engine.execute(Request::default()).await;
+        assert_eq!(res.0, json!({"response": "ok"}));
+
+        // jump to unknown task stops the flow
+        let res = engine
+            .execute(Request::new(
+                HashMap::new(),
+                JsonValue::Null,
+                HashMap::from([("skip".to_string(), "1".to_string())]),
+            ))
+            .await;
+        assert_eq!(res.0, json!({"response": null}));
+    }
+
+    #[tokio::test]
+    async fn test_flow_limits() {
+        let cyclic = serde_yaml_ng::from_str(
+            r#"
+                init:
+                  assign:
+                    x: 0
+
+                inc:
+                  assign:
+                    x: ${x + 1}
+
+                cond:
+                  switch:
+                    - condition: ${x < 0}
+                      next: result
+                  next: inc
+
+                result:
+                  return: never
+            "#,
+        )
+        .unwrap();
+        let config = EngineConfig {
+            max_steps: 50,
+            ..EngineConfig::new("./unittest_dsl")
+        };
+
+        let res = Engine::from_template(&cyclic, &config)
+            .execute(Request::default())
+            .await;
+        assert_eq!(res.1, 500);
+        assert_eq!(res.0["error"], json!("flow exceeded 50 steps"));
+        assert_eq!(res.0["task"], json!("cond"));
+
+        let upstream = httpmock::MockServer::start();
+        upstream.mock(|_, then| {
+            then.status(200).delay(Duration::from_secs(5));
+        });
+        let endpoint = Endpoint {
+            guards: vec![],
+            tag: "some".to_string(),
+            url_path: "/some/".to_string(),
+            method: rstmytype::ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::from_str(&format!(
+                r#"
+                    decl:
+                      call: declare
+                      timeout: 100ms
+
+                    fetch:
+                      call: http.get
+                      args:
+                        url: {}
+                      result: res
+
+                    result:
+                      return: ok
+                "#,
+                upstream.base_url()
+            ))
+            .unwrap(),
+            merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
+        };
+
+        let config = EngineConfig {
+            timeout: Some(Duration::from_secs(60)),
+            ..EngineConfig::new("./unittest_dsl")
+        };
+        let start = Instant::now();
+        let res = Engine::from_endpoint(&endpoint, &config)
+            .execute(Request::default())
+            .await;
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(res.1, 504);
+        assert_eq!(res.0, json!({"error": "flow timed out after 100 ms"}));
+    }
+
+    #[tokio::test]
+    async fn test_template_shares_budget() {
+        let flow = serde_yaml_ng::from_str(
+            r#"
+                call:
+                  template: test/TEMPLATES/recursive.yml
+                  result: res
+
+                result:
+                  return: ${res}
+            "#,
+        )
+        .unwrap();
+        let config = EngineConfig {
+            max_steps: 5,
+            ..EngineConfig::new("./unittest_dsl")
+        };
+
+        // template including itself runs until steps of the caller are used up
+        let res = Engine::from_template(&flow, &config)
+            .execute(Request::default())
+            .await;
+        assert_eq!(res.1, 500);
+        assert_eq!(
+            res.0,
+            json!({"error": "flow exceeded 5 steps", "task": "result"})
+        );
+
+        // or until it is nested too deep
+        let res = Engine::from_template(&flow, &EngineConfig::new("./unittest_dsl"))
+            .execute(Request::default())
+            .await;
+        assert_eq!(res.1, 200);
+        assert!(res.0.to_string().contains("templates nested deeper than 8"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_stream_limits() {
+        let upstream = httpmock::MockServer::start();
+        let calls = upstream.mock(|_, then| {
+            then.status(200).delay(Duration::from_millis(20));
+        });
+        let flow = |url: &str| {
+            serde_yaml_ng::from_str(&format!(
+                r#"
+                    fetch:
+                      call: http.get
+                      args:
+                        url: {}
+                      result: res
+
+                    progress:
+                      emit: tick
+                      next: fetch
+                "#,
+                url
+            ))
+            .unwrap()
+        };
+
+        let config = EngineConfig {
+            max_steps: 4,
+            ..EngineConfig::new("./unittest_dsl")
+        };
+        let engine = Arc::new(Engine::from_template(&flow(&upstream.base_url()), &config));
+        let resp = engine.execute_stream(Request::default()).await;
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&body),
+            concat!(
+                "data: tick\n\n",
+                "data: tick\n\n",
+                "event: error\ndata: {\"error\":\"flow exceeded 4 steps\",\"task\":\"fetch\"}\n\n",
+            )
+        );
+
+        // endless flow stops calling upstream when client goes away
+        let engine = Arc::new(Engine::from_template(
+            &flow(&upstream.base_url()),
+            &EngineConfig::new("./unittest_dsl"),
+        ));
+        let resp = engine.execute_stream(Request::default()).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(resp);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let hits = calls.hits();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(calls.hits(), hits);
+    }
 }
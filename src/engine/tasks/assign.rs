@@ -1,5 +1,5 @@
 use crate::engine::context::Context;
-use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory, render_obj};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
 use async_trait::async_trait;
 use futures::future::join_all;
 use log::warn;
@@ -10,8 +10,8 @@ pub struct AssignFactory {}
 
 #[derive(Debug)]
 pub struct Assign {
-    assign_expr: YmlValue,
-    next_task: Option<String>,
+    assign_expr: CompiledValue,
+    next_task: Option<usize>,
     name: String,
 }
 
@@ -30,7 +30,7 @@ impl TaskFactory for AssignFactory {
         let next_task = self.get_next_task(task_name, yml);
 
         Some(Box::new(Assign {
-            assign_expr: assign_exprs.clone(),
+            assign_expr: CompiledValue::from_yml(assign_exprs),
             next_task: next_task,
             name: task_name.to_string(),
         }))
@@ -46,7 +46,7 @@ impl AssignFactory {
 #[async_trait]
 impl Task for Assign {
     async fn execute(&self, context: Context) -> ExecutionResult {
-        let rendered = render_obj(&self.assign_expr, &context).await;
+        let rendered = self.assign_expr.render(&context).await;
         join_all(
             rendered
                 .as_object()
@@ -60,12 +60,16 @@ impl Task for Assign {
         )
         .await;
 
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         &self.name
     }
+
+    fn get_type(&self) -> &'static str {
+        "assign"
+    }
 }
 
 #[cfg(test)]
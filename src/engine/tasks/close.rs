@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use serde_yaml_ng::Value as YmlValue;
+
+use crate::engine::context::Expr;
+use crate::engine::context::{Context, SocketMessage};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
+
+#[derive(Debug)]
+pub struct CloseFactory {}
+
+/// closes websocket connection, flow is not continued after close
+#[derive(Debug)]
+pub struct Close {
+    code: CompiledValue,
+    reason: Option<Expr>,
+    name: String,
+}
+
+impl TaskFactory for CloseFactory {
+    fn from_yml(&self, task_name: &str, yml: &YmlValue) -> Option<Box<dyn Task>> {
+        let task_body = yml.get(task_name)?;
+        let code = CompiledValue::from_yml(task_body.get("close")?);
+        let reason = task_body
+            .get("reason")
+            .and_then(|v| v.as_str())
+            .map(Expr::parse);
+
+        Some(Box::new(Close {
+            code,
+            reason,
+            name: task_name.to_string(),
+        }))
+    }
+}
+
+impl CloseFactory {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl Task for Close {
+    async fn execute(&self, context: Context) -> ExecutionResult {
+        let code = self.code.render(&context).await.as_u64();
+
+        let reason = match &self.reason {
+            Some(reason) => match context.evaluate(reason).await {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            },
+            None => "".to_string(),
+        };
+
+        context
+            .send_socket(SocketMessage::Close(
+                code.and_then(|c| u16::try_from(c).ok()),
+                reason,
+            ))
+            .await;
+
+        ExecutionResult(context, None)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_type(&self) -> &'static str {
+        "close"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        endpoints::types::Request,
+        engine::{
+            context::{Context, SocketMessage},
+            tasks::{close::CloseFactory, task::TaskFactory},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_close_task() {
+        let factory = CloseFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          close: 4000
+                          reason: bye ${1 + 1}
+
+                        next:
+                          return: ok
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        context.set_socket_sender(tx);
+
+        let res = task.execute(context).await;
+        assert!(res.1.is_none());
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            SocketMessage::Close(Some(4000), "bye 2".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_without_code() {
+        let factory = CloseFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          close:
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        context.set_socket_sender(tx);
+
+        task.execute(context).await;
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            SocketMessage::Close(None, "".to_string())
+        );
+    }
+}
@@ -1,7 +1,12 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
+use log::warn;
+use serde_yaml_ng::Value as YmlValue;
 
 use crate::engine::context::Context;
 use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory};
+use crate::limits::parse_duration;
 
 #[derive(Debug)]
 pub struct DeclarationFactory {}
@@ -9,7 +14,7 @@ pub struct DeclarationFactory {}
 #[derive(Debug)]
 struct Declaration {
     name: String,
-    next_task: Option<String>,
+    next_task: Option<usize>,
 }
 
 impl DeclarationFactory {
@@ -35,24 +40,51 @@ impl TaskFactory for DeclarationFactory {
     }
 }
 
+/// `timeout` of the flow `declare` task, number of seconds or string like `500ms`
+pub fn declared_timeout(yml: &YmlValue) -> Option<Duration> {
+    let timeout = yml
+        .as_mapping()?
+        .values()
+        .filter(|t| t.get("call").and_then(|c| c.as_str()) == Some("declare"))
+        .find_map(|t| t.get("timeout"))?;
+
+    let parsed = match timeout {
+        YmlValue::Number(n) => parse_duration(&n.to_string()),
+        YmlValue::String(s) => parse_duration(s),
+        other => Err(format!("Invalid duration: {:?}", other)),
+    };
+    parsed
+        .map_err(|e| warn!("Ignoring declared timeout: {}", e))
+        .ok()
+}
+
 #[async_trait]
 impl Task for Declaration {
     async fn execute(&self, context: Context) -> ExecutionResult {
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         &self.name
     }
+
+    fn get_type(&self) -> &'static str {
+        "declare"
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use std::time::Duration;
+
     use crate::{
         endpoints::types::Request,
         engine::{
             context::Context,
-            tasks::{declaration::DeclarationFactory, task::TaskFactory},
+            tasks::{
+                declaration::{DeclarationFactory, declared_timeout},
+                task::TaskFactory,
+            },
         },
     };
 
@@ -95,4 +127,20 @@ mod test {
 
         task.execute(context).await;
     }
+
+    #[test]
+    fn test_declared_timeout() {
+        let timeout = |yml: &str| declared_timeout(&serde_yaml_ng::from_str(yml).unwrap());
+
+        assert_eq!(
+            timeout("decl:\n  call: declare\n  timeout: 2\n"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            timeout("decl:\n  call: declare\n  timeout: 150ms\n"),
+            Some(Duration::from_millis(150))
+        );
+        assert_eq!(timeout("decl:\n  call: declare\n  timeout: never\n"), None);
+        assert_eq!(timeout("ret:\n  return: ok\n  timeout: 2\n"), None);
+    }
 }
@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+use log::debug;
+use serde_yaml_ng::Value as YmlValue;
+
+use crate::engine::context::{Context, Expr, StreamEvent};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
+
+#[derive(Debug)]
+pub struct EmitFactory {}
+
+#[derive(Debug)]
+pub struct Emit {
+    data: CompiledValue,
+    event: Option<Expr>,
+    id: Option<Expr>,
+    next_task: Option<usize>,
+    name: String,
+}
+
+impl TaskFactory for EmitFactory {
+    fn from_yml(&self, task_name: &str, yml: &YmlValue) -> Option<Box<dyn Task>> {
+        let task_body = yml.get(task_name)?;
+        let data = CompiledValue::from_yml(task_body.get("emit")?);
+
+        let event = task_body
+            .get("event")
+            .and_then(|v| v.as_str())
+            .map(Expr::parse);
+        let id = task_body
+            .get("id")
+            .and_then(|v| v.as_str())
+            .map(Expr::parse);
+
+        let next_task = self.get_next_task(task_name, yml);
+
+        Some(Box::new(Emit {
+            data,
+            event,
+            id,
+            next_task,
+            name: task_name.to_string(),
+        }))
+    }
+}
+
+impl EmitFactory {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Emit {
+    async fn render_optional(expr: &Option<Expr>, context: &Context) -> Option<String> {
+        let rendered = context.evaluate(expr.as_ref()?).await;
+        match rendered.as_str() {
+            Some(s) => Some(s.to_string()),
+            None if rendered.is_null() => None,
+            None => Some(rendered.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl Task for Emit {
+    async fn execute(&self, context: Context) -> ExecutionResult {
+        let event = StreamEvent {
+            event: Self::render_optional(&self.event, &context).await,
+            id: Self::render_optional(&self.id, &context).await,
+            data: self.data.render(&context).await,
+        };
+
+        if !context.emit(event).await {
+            debug!(
+                "Streaming client is gone, stopping flow at task {}",
+                self.name
+            );
+            return ExecutionResult(context, None);
+        }
+
+        ExecutionResult(context, self.next_task)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_type(&self) -> &'static str {
+        "emit"
+    }
+
+    fn is_streaming(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        endpoints::types::Request,
+        engine::{
+            context::{Context, StreamEvent},
+            tasks::{emit::EmitFactory, task::TaskFactory},
+        },
+    };
+    use serde_json::json;
+
+    #[test]
+    fn factory_returns_none() {
+        let factory = EmitFactory::new();
+        let value = factory.from_yml(
+            "test",
+            &serde_yaml_ng::from_str(
+                r#"
+                    test:
+                      return: ok
+                "#,
+            )
+            .unwrap(),
+        );
+        assert!(value.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_emit_task() {
+        let factory = EmitFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          emit:
+                            done: ${1 + 1}
+                          event: progress
+                          id: ${"id-" + 2}
+
+                        next:
+                          return: ok
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert!(task.is_streaming());
+
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        context.set_events_sender(tx);
+
+        let res = task.execute(context).await;
+        assert_eq!(res.1, Some(1));
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            StreamEvent {
+                event: Some("progress".to_string()),
+                id: Some("id-2".to_string()),
+                data: json!({"done": 2}),
+            }
+        );
+
+        drop(rx);
+        let res = task.execute(res.0).await;
+        assert!(res.1.is_none());
+    }
+}
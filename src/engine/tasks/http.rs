@@ -4,10 +4,15 @@ use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
 };
 use serde_json::{Value as JsonValue, json};
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, str::FromStr, time::Instant};
+use tracing::{Instrument, Span, field, info_span};
 
-use crate::engine::context::Context;
-use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory, render_obj};
+use crate::access_log::REQUEST_ID_HEADER;
+use crate::engine::context::{Context, Expr, StreamEvent};
+use crate::engine::mocks::current_mocks;
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
+use crate::metrics::METRICS;
+use crate::telemetry::inject_trace_context;
 
 use async_trait::async_trait;
 use futures::future::join_all;
@@ -36,37 +41,57 @@ impl HttpMethod {
             Self::Put => client.put(url),
         }
     }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Delete => "DELETE",
+            Self::Patch => "PATCH",
+            Self::Put => "PUT",
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct HttpArgs {
     url: String,
+    url_expr: Expr,
     method: HttpMethod,
-    headers: HashMap<String, String>,
-    query: HashMap<String, String>,
-    body: YmlValue,
+    headers: HashMap<String, Expr>,
+    query: HashMap<String, Expr>,
+    body: CompiledValue,
 }
 
 impl HttpArgs {
     async fn render_headers(&self, context: &Context) -> HeaderMap {
-        join_all(self.headers.iter().map(async |(k, v)| {
-            Some((
-                HeaderName::from_str(k).ok()?,
-                context.evaluate_expr(v).await,
-            ))
-        }))
-        .await
-        .into_iter()
-        .flat_map(|o| o)
-        .flat_map(|(k, v)| Some((k, HeaderValue::from_str(v.as_str()?).ok()?)))
-        .collect()
+        let mut headers: HeaderMap =
+            join_all(self.headers.iter().map(async |(k, v)| {
+                Some((HeaderName::from_str(k).ok()?, context.evaluate(v).await))
+            }))
+            .await
+            .into_iter()
+            .flat_map(|o| o)
+            .flat_map(|(k, v)| Some((k, HeaderValue::from_str(v.as_str()?).ok()?)))
+            .collect();
+
+        // explicitly declared header wins
+        if let Some(request_id) = &context.request_id
+            && !headers.contains_key(REQUEST_ID_HEADER)
+            && let Ok(value) = HeaderValue::from_str(request_id)
+        {
+            headers.insert(REQUEST_ID_HEADER, value);
+        }
+        inject_trace_context(&mut headers);
+
+        headers
     }
 
     async fn render_query(&self, context: &Context) -> HashMap<String, String> {
         join_all(
             self.query
                 .iter()
-                .map(async |(k, v)| (k.to_string(), context.evaluate_expr(v).await)),
+                .map(async |(k, v)| (k.to_string(), context.evaluate(v).await)),
         )
         .await
         .into_iter()
@@ -75,7 +100,12 @@ impl HttpArgs {
     }
 
     async fn render_body(&self, context: &Context) -> JsonValue {
-        render_obj(&self.body, context).await
+        self.body.render(context).await
+    }
+
+    async fn render_url(&self, context: &Context) -> String {
+        let evaluate_result = context.evaluate(&self.url_expr).await;
+        evaluate_result.as_str().unwrap_or(&self.url).to_string()
     }
 
     async fn render_response(&self, response: Response) -> JsonValue {
@@ -91,31 +121,228 @@ impl HttpArgs {
         })
     }
 
-    async fn do_request(&self, context: &Context) -> JsonValue {
-        let evaluate_result = context.evaluate_expr(&self.url).await;
-        let url = evaluate_result.as_str().unwrap_or(&self.url);
-        let request_result = self
+    /// url and status are recorded by `send_request`
+    fn span(&self) -> Span {
+        info_span!(
+            "http",
+            otel.name = self.method.as_str(),
+            otel.kind = "client",
+            otel.status_code = field::Empty,
+            http.request.method = self.method.as_str(),
+            http.response.status_code = field::Empty,
+            url.full = field::Empty,
+        )
+    }
+
+    async fn send_request(&self, url: &str, context: &Context) -> Option<Response> {
+        let span = Span::current();
+        span.record("url.full", url);
+
+        // test suites answer with canned responses, nothing leaves the process
+        if let Some(mocks) = current_mocks() {
+            let query = self.render_query(context).await;
+            let response = mocks.respond(self.method.as_str(), url, &query);
+            match &response {
+                Some(r) => span.record("http.response.status_code", r.status().as_u16()),
+                None => span.record("otel.status_code", "error"),
+            };
+            return response;
+        }
+
+        let request = self
             .method
             .to_request_builder(url)
             .headers(self.render_headers(&context).await)
             .query(&self.render_query(context).await)
-            .json(&self.render_body(context).await)
-            .send()
-            .await;
+            .json(&self.render_body(context).await);
+
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_default();
+        let start = Instant::now();
+        let request_result = request.send().await;
+        METRICS.observe_upstream(
+            self.method.as_str(),
+            &host,
+            request_result.as_ref().ok().map(|r| r.status().as_u16()),
+            start.elapsed(),
+        );
 
         let Ok(response) = request_result else {
-            warn!("request to {} failed", &self.url);
+            warn!("request to {} failed", url);
+            span.record("otel.status_code", "error");
+            return None;
+        };
+
+        span.record("http.response.status_code", response.status().as_u16());
+        if response.status().is_server_error() {
+            span.record("otel.status_code", "error");
+        }
+
+        Some(response)
+    }
+
+    async fn do_request(&self, context: &Context) -> JsonValue {
+        async {
+            let url = self.render_url(context).await;
+            let Some(response) = self.send_request(&url, context).await else {
+                return JsonValue::Null;
+            };
+
+            self.render_response(response).await
+        }
+        .instrument(self.span())
+        .await
+    }
+
+    /// relays upstream body to the streaming client. `text/event-stream` upstream
+    /// is relayed event by event, any other body is relayed chunk by chunk
+    async fn relay_stream(&self, context: &Context) -> JsonValue {
+        let url = self.render_url(context).await;
+        let Some(mut response) = self.send_request(&url, context).await else {
             return JsonValue::Null;
         };
 
-        self.render_response(response).await
+        let status = response.status().as_u16();
+        let is_event_stream = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+
+        let mut decoder = ChunkDecoder::default();
+        while let Ok(Some(chunk)) = response.chunk().await {
+            if !is_event_stream {
+                let text = decoder.text(&chunk);
+                if text.is_empty() {
+                    continue;
+                }
+                let event = StreamEvent {
+                    event: None,
+                    id: None,
+                    data: JsonValue::String(text),
+                };
+                if !context.emit(event).await {
+                    break;
+                }
+                continue;
+            }
+
+            let mut client_is_gone = false;
+            for event in decoder.events(&chunk) {
+                if !context.emit(event).await {
+                    client_is_gone = true;
+                    break;
+                }
+            }
+            if client_is_gone {
+                break;
+            }
+        }
+
+        json!({
+            "request": {
+                "url": self.url,
+            },
+            "response": {
+                "status": status,
+            }
+        })
     }
 }
 
+/// bytes at the end which start a utf-8 character not finished yet
+fn incomplete_tail(bytes: &[u8]) -> usize {
+    for k in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - k];
+        if byte & 0xC0 == 0x80 {
+            // continuation byte, the character starts before it
+            continue;
+        }
+        let len = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if len > k { k } else { 0 };
+    }
+    0
+}
+
+/// upstream chunks may split a character or `\r\n` anywhere
+#[derive(Debug, Default)]
+struct ChunkDecoder {
+    bytes: Vec<u8>,
+    /// event stream text with `\n` line endings, `\r` at the end may start `\r\n`
+    buffer: String,
+}
+
+impl ChunkDecoder {
+    /// text of complete characters, the rest waits for the next chunk
+    fn text(&mut self, chunk: &[u8]) -> String {
+        self.bytes.extend_from_slice(chunk);
+        let complete = self.bytes.len() - incomplete_tail(&self.bytes);
+        let text = String::from_utf8_lossy(&self.bytes[..complete]).into_owned();
+        self.bytes.drain(..complete);
+        text
+    }
+
+    /// events completed by the chunk
+    fn events(&mut self, chunk: &[u8]) -> Vec<StreamEvent> {
+        let text = self.text(chunk);
+        self.buffer.push_str(&text);
+        let pending_cr = self.buffer.ends_with('\r');
+        if pending_cr {
+            self.buffer.pop();
+        }
+        self.buffer = self.buffer.replace("\r\n", "\n").replace('\r', "\n");
+
+        let mut events = vec![];
+        while let Some((block, rest)) = self.buffer.split_once("\n\n") {
+            events.extend(parse_sse_block(block));
+            self.buffer = rest.to_string();
+        }
+        if pending_cr {
+            self.buffer.push('\r');
+        }
+        events
+    }
+}
+
+fn parse_sse_block(block: &str) -> Option<StreamEvent> {
+    let mut event = None;
+    let mut id = None;
+    let mut data: Vec<&str> = Vec::new();
+
+    for line in block.lines() {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = Some(value.to_string()),
+            "id" => id = Some(value.to_string()),
+            "data" => data.push(value),
+            _ => {} // comments and retry are not relayed
+        }
+    }
+
+    if data.is_empty() {
+        return None;
+    }
+
+    Some(StreamEvent {
+        event,
+        id,
+        data: JsonValue::String(data.join("\n")),
+    })
+}
+
 #[derive(Debug)]
 pub struct Http {
-    next_task: Option<String>,
+    next_task: Option<usize>,
     name: String,
+    stream: bool,
 
     result: Option<String>,
     args: HttpArgs,
@@ -154,7 +381,7 @@ impl HttpFactory {
             .flat_map(|v| v.as_mapping())
             .flat_map(|v| v.iter())
             .flat_map(|(k, v)| Some((k.as_str()?, v.as_str()?)))
-            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .map(|(k, v)| (k.to_string(), Expr::parse(v)))
             .collect();
 
         let query = yml
@@ -163,12 +390,13 @@ impl HttpFactory {
             .flat_map(|v| v.as_mapping())
             .flat_map(|v| v.iter())
             .flat_map(|(k, v)| Some((k.as_str()?, v.as_str()?)))
-            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .map(|(k, v)| (k.to_string(), Expr::parse(v)))
             .collect();
 
-        let body = yml.get("body").map(|y| y.clone()).unwrap_or(YmlValue::Null);
+        let body = CompiledValue::from_yml(yml.get("body").unwrap_or(&YmlValue::Null));
 
         Some(HttpArgs {
+            url_expr: Expr::parse(&url),
             url,
             method,
             headers,
@@ -188,10 +416,16 @@ impl TaskFactory for HttpFactory {
             .and_then(|y| y.get("result"))
             .and_then(|y| y.as_str())
             .map(|s| s.to_string());
+        let stream = yml
+            .get(task_name)
+            .and_then(|y| y.get("stream"))
+            .and_then(|y| y.as_bool())
+            .unwrap_or(false);
         let args = self.parse_http_args(yml.get(task_name)?.get("args")?, method)?;
         Some(Box::new(Http {
             next_task,
             name,
+            stream,
             result,
             args,
         }))
@@ -201,7 +435,12 @@ impl TaskFactory for HttpFactory {
 #[async_trait]
 impl Task for Http {
     async fn execute(&self, context: Context) -> ExecutionResult {
-        let response = self.args.do_request(&context).await;
+        let response = if self.stream {
+            let span = self.args.span();
+            self.args.relay_stream(&context).instrument(span).await
+        } else {
+            self.args.do_request(&context).await
+        };
 
         if let Some(result_name) = &self.result {
             context
@@ -212,12 +451,20 @@ impl Task for Http {
                 )))
                 .await;
         }
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         &self.name
     }
+
+    fn get_type(&self) -> &'static str {
+        "http"
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.stream
+    }
 }
 
 #[cfg(test)]
@@ -225,8 +472,11 @@ mod test {
     use crate::{
         endpoints::types::Request,
         engine::{
-            context::Context,
-            tasks::{http::HttpFactory, task::TaskFactory},
+            context::{Context, StreamEvent},
+            tasks::{
+                http::{ChunkDecoder, HttpFactory, parse_sse_block},
+                task::TaskFactory,
+            },
         },
     };
     use httpmock::{Method, prelude::*};
@@ -354,4 +604,123 @@ mod test {
         let response = ctx.evaluate_expr("${res.response.body.ok}").await;
         assert_eq!(response, "is ok!");
     }
+
+    #[tokio::test]
+    async fn test_http_forwards_request_id() {
+        let test_server = MockServer::start_async().await;
+        let mock = test_server
+            .mock_async(|when, then| {
+                when.path("/test")
+                    .method(GET)
+                    .header("x-request-id", "req-1");
+                then.body(json!({"ok": "is ok!"}).to_string()).status(200);
+            })
+            .await;
+
+        let factory = HttpFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(&format!(
+                    r#"
+                        test:
+                          call: http.get
+                          args:
+                            url: {}
+                          result: res
+                    "#,
+                    test_server.url("/test")
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        context.request_id = Some("req-1".to_string());
+
+        task.execute(context).await;
+        mock.assert();
+    }
+
+    #[test]
+    fn test_parse_sse_block() {
+        assert_eq!(
+            parse_sse_block("event: token\nid: 1\ndata: a\ndata: b"),
+            Some(StreamEvent {
+                event: Some("token".to_string()),
+                id: Some("1".to_string()),
+                data: json!("a\nb"),
+            })
+        );
+        assert_eq!(parse_sse_block(": keep-alive comment"), None);
+    }
+
+    #[test]
+    fn test_chunk_decoder() {
+        let token = "привет".as_bytes();
+        let mut decoder = ChunkDecoder::default();
+        assert_eq!(decoder.text(&token[..3]), "п");
+        assert_eq!(decoder.text(&token[3..]), "ривет");
+
+        let mut decoder = ChunkDecoder::default();
+        let body = "data: привет\r\n\r\ndata: b\r\n\r\n".as_bytes();
+        // split inside a character and between `\r` and `\n`
+        assert!(decoder.events(&body[..9]).is_empty());
+        assert!(decoder.events(&body[9..19]).is_empty());
+        let events = decoder.events(&body[19..]);
+        assert_eq!(
+            events.iter().map(|e| e.data.clone()).collect::<Vec<_>>(),
+            vec![json!("привет"), json!("b")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_stream_relay() {
+        let test_server = MockServer::start_async().await;
+        let mock = test_server
+            .mock_async(|when, then| {
+                when.path("/events").method(GET);
+                then.header("content-type", "text/event-stream")
+                    .body("event: token\ndata: hello\n\n: ping\n\ndata: world\n\n")
+                    .status(200);
+            })
+            .await;
+
+        let factory = HttpFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(&format!(
+                    r#"
+                        test:
+                          call: http.get
+                          stream: true
+                          args:
+                            url: {}
+                          result: res
+                    "#,
+                    test_server.url("/events")
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+        assert!(task.is_streaming());
+
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        context.set_events_sender(tx);
+
+        let res = task.execute(context).await;
+        mock.assert();
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.event.unwrap(), "token");
+        assert_eq!(first.data, "hello");
+        let second = rx.recv().await.unwrap();
+        assert!(second.event.is_none());
+        assert_eq!(second.data, "world");
+
+        let status = res.0.evaluate_expr("${res.response.status}").await;
+        assert_eq!(status, 200);
+    }
 }
@@ -1,5 +1,5 @@
 use crate::engine::context::Context;
-use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory, render_obj};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
 use async_trait::async_trait;
 use serde_yaml_ng::Value as YmlValue;
 use tokio::time::{Duration, sleep};
@@ -9,9 +9,9 @@ pub struct MockFactory {}
 
 #[derive(Debug)]
 pub struct Mock {
-    args: YmlValue,
+    args: CompiledValue,
     result: Option<String>,
-    next_task: Option<String>,
+    next_task: Option<usize>,
     name: String,
     sleep_mcs: u64,
 }
@@ -24,7 +24,7 @@ impl TaskFactory for MockFactory {
             return None;
         }
 
-        let args = task_body.get("args")?.clone();
+        let args = CompiledValue::from_yml(task_body.get("args")?);
         let result = task_body
             .get("result")
             .and_then(|v| v.as_str())
@@ -52,7 +52,7 @@ impl MockFactory {
 #[async_trait]
 impl Task for Mock {
     async fn execute(&self, context: Context) -> ExecutionResult {
-        let rendered = render_obj(&self.args, &context).await;
+        let rendered = self.args.render(&context).await;
 
         if let Some(res) = &self.result {
             context
@@ -68,12 +68,16 @@ impl Task for Mock {
             sleep(Duration::from_millis(self.sleep_mcs)).await;
         }
 
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         &self.name
     }
+
+    fn get_type(&self) -> &'static str {
+        "mock"
+    }
 }
 
 #[cfg(test)]
@@ -1,25 +1,34 @@
+use std::sync::LazyLock;
+
 use serde_yaml_ng::Value as YmlValue;
 
 use crate::engine::tasks::assign::AssignFactory;
+use crate::engine::tasks::close::CloseFactory;
 use crate::engine::tasks::declaration::DeclarationFactory;
+use crate::engine::tasks::emit::EmitFactory;
 use crate::engine::tasks::http::HttpFactory;
 use crate::engine::tasks::mock::MockFactory;
 use crate::engine::tasks::ret::RetFactory;
+use crate::engine::tasks::send::SendFactory;
 use crate::engine::tasks::switch::SwitchFactory;
 use crate::engine::tasks::task::{Task, TaskFactory};
 use crate::engine::tasks::template::TemplateFactory;
 
 mod assign;
-mod declaration;
+mod close;
+pub mod declaration;
+mod emit;
 mod http;
 mod mock;
 mod ret;
+mod send;
 mod switch;
 pub mod task;
 mod template;
 
-pub fn produce_task(task_name: &str, global_value: &YmlValue) -> Option<Box<dyn Task>> {
-    let factories: Vec<Box<dyn TaskFactory>> = vec![
+/// factories are stateless, so they are built once
+static FACTORIES: LazyLock<Vec<Box<dyn TaskFactory>>> = LazyLock::new(|| {
+    vec![
         Box::new(DeclarationFactory::new()),
         Box::new(RetFactory::new()),
         Box::new(AssignFactory::new()),
@@ -27,9 +36,14 @@ pub fn produce_task(task_name: &str, global_value: &YmlValue) -> Option<Box<dyn
         Box::new(HttpFactory::new()),
         Box::new(MockFactory::new()),
         Box::new(TemplateFactory::new()),
-    ];
+        Box::new(EmitFactory::new()),
+        Box::new(SendFactory::new()),
+        Box::new(CloseFactory::new()),
+    ]
+});
 
-    factories
+pub fn produce_task(task_name: &str, global_value: &YmlValue) -> Option<Box<dyn Task>> {
+    FACTORIES
         .iter()
         .flat_map(|f| f.from_yml(task_name, global_value))
         .next() // returns first successfull parsed task
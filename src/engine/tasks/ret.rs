@@ -1,7 +1,10 @@
-use crate::engine::context::Context;
-use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory, render_obj};
+use crate::engine::context::{BodyFormat, Context, Expr};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
 
 use async_trait::async_trait;
+use log::warn;
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use serde_json::Value as JsonValue;
 use serde_yaml_ng::Value as YmlValue;
 
 #[derive(Debug)]
@@ -9,16 +12,21 @@ pub struct RetFactory {}
 
 #[derive(Debug)]
 pub struct Ret {
-    return_expr: YmlValue,
+    return_expr: CompiledValue,
     status_code: u16,
-    next_task: Option<String>,
+    headers: CompiledValue,
+    cookies: CompiledValue,
+    content_type: Option<Expr>,
+    wrapper: Option<bool>,
+    format: BodyFormat,
+    next_task: Option<usize>,
     name: String,
 }
 
 impl TaskFactory for RetFactory {
     fn from_yml(&self, task_name: &str, yml: &serde_yaml_ng::Value) -> Option<Box<dyn Task>> {
         let task_body = yml.get(task_name)?;
-        let return_expr = task_body.get("return")?.clone();
+        let return_expr = CompiledValue::from_yml(task_body.get("return")?);
 
         let status_code: u16 = task_body
             .get("status")
@@ -26,11 +34,34 @@ impl TaskFactory for RetFactory {
             .and_then(|v| v.try_into().ok())
             .unwrap_or(200);
 
+        let headers = task_body
+            .get("headers")
+            .filter(|h| h.is_mapping())
+            .map(CompiledValue::from_yml)
+            .unwrap_or(CompiledValue::Json(JsonValue::Null));
+        let cookies = task_body
+            .get("cookies")
+            .filter(|c| c.is_mapping())
+            .map(CompiledValue::from_yml)
+            .unwrap_or(CompiledValue::Json(JsonValue::Null));
+        let content_type = task_body
+            .get("contentType")
+            .and_then(|v| v.as_str())
+            .map(Expr::parse);
+
+        let wrapper = task_body.get("wrapper").and_then(|v| v.as_bool());
+        let format = self.parse_format(task_name, task_body);
+
         let next_task = self.get_next_task(task_name, yml);
 
         Some(Box::new(Ret {
             return_expr,
             status_code,
+            headers,
+            cookies,
+            content_type,
+            wrapper,
+            format,
             next_task,
             name: task_name.to_string(),
         }))
@@ -41,20 +72,140 @@ impl RetFactory {
     pub fn new() -> Self {
         Self {}
     }
+
+    fn parse_format(&self, task_name: &str, task_body: &YmlValue) -> BodyFormat {
+        match task_body.get("format").and_then(|v| v.as_str()) {
+            None | Some("json") => BodyFormat::Json,
+            Some("text") => BodyFormat::Text,
+            Some("base64") => BodyFormat::Base64,
+            Some(other) => {
+                warn!(
+                    "Unknown return format \"{}\" in task {}, using json",
+                    other, task_name
+                );
+                BodyFormat::Json
+            }
+        }
+    }
+}
+
+fn header_values(value: &JsonValue) -> Vec<String> {
+    match value {
+        JsonValue::Null => vec![],
+        JsonValue::String(s) => vec![s.clone()],
+        JsonValue::Array(values) => values.iter().flat_map(header_values).collect(),
+        other => vec![other.to_string()],
+    }
+}
+
+/// cookie-octets of RFC 6265, anything else in a value is percent-encoded
+const COOKIE_VALUE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b',')
+    .add(b';')
+    .add(b'\\')
+    .add(b'%');
+
+fn encode_cookie_value(values: Vec<String>) -> String {
+    utf8_percent_encode(&values.join(""), COOKIE_VALUE).to_string()
+}
+
+/// attribute values cannot be encoded, the ones able to add attributes are dropped
+fn is_safe_attr(value: &str) -> bool {
+    !value.contains(';') && !value.chars().any(|c| c.is_control())
+}
+
+fn render_cookie(name: &str, cookie: &JsonValue) -> String {
+    let Some(attrs) = cookie.as_object() else {
+        return format!("{}={}", name, encode_cookie_value(header_values(cookie)));
+    };
+
+    let value = attrs.get("value").map(header_values).unwrap_or_default();
+    let mut rendered = format!("{}={}", name, encode_cookie_value(value));
+
+    for (attr, key) in [
+        ("Path", "path"),
+        ("Domain", "domain"),
+        ("Max-Age", "maxAge"),
+        ("Expires", "expires"),
+        ("SameSite", "sameSite"),
+    ] {
+        let Some(v) = attrs
+            .get(key)
+            .map(header_values)
+            .and_then(|v| v.first().cloned())
+        else {
+            continue;
+        };
+
+        if !is_safe_attr(&v) {
+            warn!("Skipping unsafe {} of cookie {}: {:?}", attr, name, v);
+            continue;
+        }
+        rendered.push_str(&format!("; {}={}", attr, v));
+    }
+
+    for (attr, key) in [("Secure", "secure"), ("HttpOnly", "httpOnly")] {
+        if attrs.get(key).and_then(|v| v.as_bool()) == Some(true) {
+            rendered.push_str(&format!("; {}", attr));
+        }
+    }
+
+    rendered
+}
+
+impl Ret {
+    async fn render_headers(&self, context: &Context) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = self
+            .headers
+            .render(context)
+            .await
+            .as_object()
+            .iter()
+            .flat_map(|h| h.iter())
+            .flat_map(|(k, v)| header_values(v).into_iter().map(|v| (k.clone(), v)))
+            .collect();
+
+        let cookies = self.cookies.render(context).await;
+        headers.extend(
+            cookies
+                .as_object()
+                .iter()
+                .flat_map(|c| c.iter())
+                .map(|(k, v)| ("set-cookie".to_string(), render_cookie(k, v))),
+        );
+
+        if let Some(content_type) = &self.content_type {
+            let rendered = context.evaluate(content_type).await;
+            if let Some(v) = header_values(&rendered).first() {
+                headers.push(("content-type".to_string(), v.clone()));
+            }
+        }
+
+        headers
+    }
 }
 
 #[async_trait]
 impl Task for Ret {
     async fn execute(&self, mut context: Context) -> ExecutionResult {
-        let return_value = render_obj(&self.return_expr, &context).await;
+        let return_value = self.return_expr.render(&context).await;
+        let headers = self.render_headers(&context).await;
         context.set_return_value(self.status_code, return_value);
+        context.set_return_headers(headers);
+        context.set_return_format(self.wrapper, self.format);
 
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         &self.name
     }
+
+    fn get_type(&self) -> &'static str {
+        "return"
+    }
 }
 
 #[cfg(test)]
@@ -62,7 +213,7 @@ mod test {
     use crate::{
         endpoints::types::Request,
         engine::{
-            context::Context,
+            context::{BodyFormat, Context},
             tasks::{ret::RetFactory, task::TaskFactory},
         },
     };
@@ -187,6 +338,120 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn test_return_headers_and_cookies() {
+        let factory = RetFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          return: moved
+                          status: 302
+                          contentType: text/plain
+                          headers:
+                            location: /some/${1 + 1}
+                            cache-control: no-cache
+                            x-multi:
+                              - a
+                              - b
+                          cookies:
+                            simple: ${"value"}
+                            injected: ${"v; Domain=evil.com, x"}
+                            session:
+                              value: abc
+                              path: /
+                              maxAge: 3600
+                              sameSite: Lax
+                              secure: true
+                              httpOnly: true
+                            pathless:
+                              value: a b
+                              path: /; Domain=evil.com
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let context = Context::from_request(Request::default(), "./unittest_dsl").await;
+
+        let res = task.execute(context).await;
+        let res_v = res.0.get_return_value();
+        assert_eq!(res_v.status, 302);
+
+        let headers: Vec<(&str, &str)> = res_v
+            .headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                ("cache-control", "no-cache"),
+                ("location", "/some/2"),
+                ("x-multi", "a"),
+                ("x-multi", "b"),
+                ("set-cookie", "injected=v%3B%20Domain=evil.com%2C%20x"),
+                ("set-cookie", "pathless=a%20b"),
+                (
+                    "set-cookie",
+                    "session=abc; Path=/; Max-Age=3600; SameSite=Lax; Secure; HttpOnly"
+                ),
+                ("set-cookie", "simple=value"),
+                ("content-type", "text/plain"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_return_format() {
+        let factory = RetFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          return: <h1>hello</h1>
+                          format: text
+                          wrapper: false
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let context = Context::from_request(Request::default(), "./unittest_dsl").await;
+
+        let res = task.execute(context).await;
+        let res_v = res.0.get_return_value();
+        assert_eq!(res_v.format, BodyFormat::Text);
+        assert_eq!(res_v.wrapper, Some(false));
+
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          return: ok
+                          format: unknown
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let context = Context::from_request(Request::default(), "./unittest_dsl").await;
+
+        let res = task.execute(context).await;
+        let res_v = res.0.get_return_value();
+        assert_eq!(res_v.format, BodyFormat::Json);
+        assert_eq!(res_v.wrapper, None);
+    }
+
     #[tokio::test]
     async fn test_return_complex_value() {
         let factory = RetFactory::new();
@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use log::debug;
+use serde_yaml_ng::Value as YmlValue;
+
+use crate::engine::context::{Context, SocketMessage};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
+
+#[derive(Debug)]
+pub struct SendFactory {}
+
+#[derive(Debug)]
+pub struct Send {
+    data: CompiledValue,
+    next_task: Option<usize>,
+    name: String,
+}
+
+impl TaskFactory for SendFactory {
+    fn from_yml(&self, task_name: &str, yml: &YmlValue) -> Option<Box<dyn Task>> {
+        let data = CompiledValue::from_yml(yml.get(task_name)?.get("send")?);
+        let next_task = self.get_next_task(task_name, yml);
+
+        Some(Box::new(Send {
+            data,
+            next_task,
+            name: task_name.to_string(),
+        }))
+    }
+}
+
+impl SendFactory {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[async_trait]
+impl Task for Send {
+    async fn execute(&self, context: Context) -> ExecutionResult {
+        let data = self.data.render(&context).await;
+
+        if !context.send_socket(SocketMessage::Send(data)).await {
+            debug!("Websocket is closed, stopping flow at task {}", self.name);
+            return ExecutionResult(context, None);
+        }
+
+        ExecutionResult(context, self.next_task)
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_type(&self) -> &'static str {
+        "send"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        endpoints::types::Request,
+        engine::{
+            context::{Context, SocketMessage},
+            tasks::{send::SendFactory, task::TaskFactory},
+        },
+    };
+    use serde_json::json;
+
+    #[test]
+    fn factory_returns_none() {
+        let factory = SendFactory::new();
+        let value = factory.from_yml(
+            "test",
+            &serde_yaml_ng::from_str(
+                r#"
+                    test:
+                      return: ok
+                "#,
+            )
+            .unwrap(),
+        );
+        assert!(value.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_send_task() {
+        let factory = SendFactory::new();
+        let task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          send:
+                            echo: ${1 + 1}
+
+                        next:
+                          return: ok
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let mut context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+        context.set_socket_sender(tx);
+
+        let res = task.execute(context).await;
+        assert_eq!(res.1, Some(1));
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            SocketMessage::Send(json!({"echo": 2}))
+        );
+
+        drop(rx);
+        let res = task.execute(res.0).await;
+        assert!(res.1.is_none());
+    }
+}
@@ -1,5 +1,5 @@
-use crate::engine::context::Context;
-use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory};
+use crate::engine::context::{Context, Expr};
+use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory, task_index};
 use async_trait::async_trait;
 use log::warn;
 
@@ -8,14 +8,15 @@ pub struct SwitchFactory {}
 
 #[derive(Debug)]
 struct SwitchCondition {
-    condition: String,
-    next_task: String,
+    condition: Expr,
+    /// missing task stops the flow
+    next_task: Option<usize>,
 }
 
 #[derive(Debug)]
 pub struct Switch {
     conditions: Vec<SwitchCondition>,
-    next_task: Option<String>,
+    next_task: Option<usize>,
     name: String,
 }
 
@@ -38,8 +39,8 @@ impl TaskFactory for SwitchFactory {
                     );
                 }
                 Some(SwitchCondition {
-                    condition,
-                    next_task: m.get("next")?.as_str()?.to_string(),
+                    condition: Expr::parse(&condition),
+                    next_task: task_index(m.get("next")?.as_str()?, yml),
                 })
             })
             .collect();
@@ -64,20 +65,24 @@ impl SwitchFactory {
 impl Task for Switch {
     async fn execute(&self, context: Context) -> ExecutionResult {
         for condition in &self.conditions {
-            let executed_value = context.evaluate_expr(&condition.condition).await.as_bool();
+            let executed_value = context.evaluate(&condition.condition).await.as_bool();
             if let Some(b) = executed_value
                 && b
             {
-                return ExecutionResult(context, Some(condition.next_task.clone()));
+                return ExecutionResult(context, condition.next_task);
             }
         }
 
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         &self.name
     }
+
+    fn get_type(&self) -> &'static str {
+        "switch"
+    }
 }
 
 #[cfg(test)]
@@ -125,6 +130,13 @@ mod test {
                               next: two
                             - condition: ${incorrect}
                           next: third
+
+                        one:
+                          return: one
+                        two:
+                          return: two
+                        third:
+                          return: third
                     "#,
                 )
                 .unwrap_or(YmlValue::Null),
@@ -137,18 +149,18 @@ mod test {
             .evaluate_expr(&Context::wrap_js_code("var some = 1;"))
             .await;
         let res = task.execute(context).await;
-        assert_eq!(res.1.unwrap(), "one");
+        assert_eq!(res.1, Some(1));
 
         res.0
             .evaluate_expr(&Context::wrap_js_code("var some = 2;"))
             .await;
         let res = task.execute(res.0).await;
-        assert_eq!(res.1.unwrap(), "two");
+        assert_eq!(res.1, Some(2));
 
         res.0
             .evaluate_expr(&Context::wrap_js_code("var some = 3;"))
             .await;
         let res = task.execute(res.0).await;
-        assert_eq!(res.1.unwrap(), "third");
+        assert_eq!(res.1, Some(3));
     }
 }
@@ -7,74 +7,125 @@ use std::fmt::Debug;
 use futures::future::join_all;
 use serde_yaml_ng::Value as YmlValue;
 
-use crate::engine::context::Context;
+use crate::engine::EngineConfig;
+use crate::engine::context::{Context, Expr};
 
-pub struct ExecutionResult(pub Context, pub Option<String>); // I'm tired fighting with borrow checker
-// next task will not be a ptr to a task, but a name of task
-// same with Option<&str> in async traits. I will clone str instead.
+/// next task is the position of its key in the flow, resolved when the flow is compiled
+pub struct ExecutionResult(pub Context, pub Option<usize>);
 
 #[async_trait]
 pub trait Task: Debug + Send + Sync {
     async fn execute(&self, context: Context) -> ExecutionResult;
 
     fn get_name(&self) -> &str;
+
+    /// task kind used in metrics and diagrams, e.g. `assign` or `http`
+    fn get_type(&self) -> &'static str;
+
+    /// task produces server-sent events, endpoint has to respond with a stream
+    fn is_streaming(&self) -> bool {
+        false
+    }
+
+    /// called once when the flow is compiled, e.g. templates compile their flows here
+    fn prepare(&mut self, _config: &EngineConfig) {}
 }
 
-pub trait TaskFactory: Debug {
+pub trait TaskFactory: Debug + Send + Sync {
     fn from_yml(&self, task_name: &str, yml: &YmlValue) -> Option<Box<dyn Task>>;
 
-    fn get_next_task(&self, task_name: &str, yml: &YmlValue) -> Option<String> {
-        let next_task = yml.get(task_name)?.get("next").and_then(|v| v.as_str());
-        if let Some(v) = next_task {
-            if v == "end" {
-                return None;
-            }
-            return Some(v.to_string());
+    fn get_next_task(&self, task_name: &str, yml: &YmlValue) -> Option<usize> {
+        task_index(&next_task_name(task_name, yml)?, yml)
+    }
+}
+
+/// position of the task in the flow, tasks are compiled in the order of their keys
+pub fn task_index(task_name: &str, yml: &YmlValue) -> Option<usize> {
+    yml.as_mapping()?
+        .keys()
+        .position(|k| k.as_str() == Some(task_name))
+}
+
+/// explicit `next` of the task or the task following it in the flow, `end` stops the flow
+pub fn next_task_name(task_name: &str, yml: &YmlValue) -> Option<String> {
+    let next_task = yml.get(task_name)?.get("next").and_then(|v| v.as_str());
+    if let Some(v) = next_task {
+        if v == "end" {
+            return None;
         }
-        let mut next_task_is_next = false;
-        for key in yml.as_mapping()?.keys() {
-            if next_task_is_next {
-                return Some(key.as_str()?.to_string());
-            }
-            if let Some(k) = key.as_str() {
-                if k == task_name {
-                    next_task_is_next = true;
-                }
+        return Some(v.to_string());
+    }
+    let mut next_task_is_next = false;
+    for key in yml.as_mapping()?.keys() {
+        if next_task_is_next {
+            return Some(key.as_str()?.to_string());
+        }
+        if let Some(k) = key.as_str() {
+            if k == task_name {
+                next_task_is_next = true;
             }
         }
-        return None;
     }
+    return None;
 }
 
-pub async fn render_obj(yml: &YmlValue, context: &Context) -> JsonValue {
-    match yml {
-        YmlValue::Bool(v) => JsonValue::Bool(v.clone()),
-        YmlValue::Mapping(m) => {
-            JsonValue::Object(
-                join_all(m.iter().map(async |(k, v)| {
-                    Some((k.as_str()?.to_string(), render_obj(v, context).await))
-                }))
+/// yml value with expressions parsed once, rendered to json on every execution
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompiledValue {
+    Json(JsonValue),
+    Expr(Expr),
+    Object(Vec<(String, CompiledValue)>),
+    Array(Vec<CompiledValue>),
+}
+
+impl CompiledValue {
+    pub fn from_yml(yml: &YmlValue) -> Self {
+        match yml {
+            YmlValue::Bool(v) => Self::Json(JsonValue::Bool(*v)),
+            YmlValue::Mapping(m) => Self::Object(
+                m.iter()
+                    .flat_map(|(k, v)| Some((k.as_str()?.to_string(), Self::from_yml(v))))
+                    .collect(),
+            ),
+            YmlValue::Null => Self::Json(JsonValue::Null),
+            YmlValue::Number(v) => Self::Json(
+                serde_json::to_value(v)
+                    .map_err(|e| {
+                        warn!("can't cast {} number to json: {}", v, e);
+                        e
+                    })
+                    .unwrap_or(JsonValue::Null),
+            ),
+            YmlValue::Sequence(v) => Self::Array(v.iter().map(Self::from_yml).collect()),
+            YmlValue::String(s) => match Expr::parse(s) {
+                Expr::Literal(s) => Self::Json(JsonValue::String(s)),
+                expr => Self::Expr(expr),
+            },
+            YmlValue::Tagged(_) => {
+                warn!("Tags not supported by json: {:?}", yml);
+                Self::Json(JsonValue::Null)
+            } // not supported by JSON
+        }
+    }
+
+    pub async fn render(&self, context: &Context) -> JsonValue {
+        match self {
+            Self::Json(v) => v.clone(),
+            Self::Expr(expr) => context.evaluate(expr).await,
+            Self::Object(fields) => JsonValue::Object(
+                join_all(
+                    fields
+                        .iter()
+                        .map(async |(k, v)| (k.clone(), Box::pin(v.render(context)).await)),
+                )
                 .await
                 .into_iter()
-                .flat_map(|o| o)
                 .collect(),
-            )
-        }
-        YmlValue::Null => JsonValue::Null,
-        YmlValue::Number(v) => serde_json::to_value(v)
-            .map_err(|e| {
-                warn!("can't cast {} number to json: {}", v, e);
-                e
-            })
-            .unwrap_or(JsonValue::Null),
-        YmlValue::Sequence(v) => {
-            JsonValue::Array(join_all(v.iter().map(async |el| render_obj(el, context).await)).await)
+            ),
+            Self::Array(v) => JsonValue::Array(
+                join_all(v.iter().map(async |el| Box::pin(el.render(context)).await)).await,
+            ),
         }
-        YmlValue::String(s) => context.evaluate_expr(s).await,
-        YmlValue::Tagged(_) => {
-            warn!("Tags not supported by json: {:?}", yml);
-            JsonValue::Null
-        } // not supported by JSON
     }
 }
 
@@ -132,7 +183,7 @@ mod test {
         endpoints::types::Request,
         engine::{
             context::Context,
-            tasks::task::{preprocess_obj, render_obj},
+            tasks::task::{CompiledValue, preprocess_obj},
         },
     };
     use serde_yaml_ng::Value as YmlValue;
@@ -282,7 +333,7 @@ mod test {
         )
         .unwrap();
 
-        let v = render_obj(&yml, &context).await;
+        let v = CompiledValue::from_yml(&yml).render(&context).await;
         let obj = v.get("obj").unwrap();
         assert!(obj.is_object());
         assert_eq!(obj.get("str").unwrap(), "some string");
@@ -1,13 +1,22 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use futures::future::join_all;
+use log::{error, warn};
+use serde_json::json;
 use serde_yaml_ng::Value as YmlValue;
 
 use crate::endpoints::types::Request;
-use crate::engine::Engine;
-use crate::engine::context::Context;
-use crate::engine::tasks::task::{ExecutionResult, Task, TaskFactory, render_obj};
+use crate::engine::context::{Context, Expr};
+use crate::engine::tasks::task::{CompiledValue, ExecutionResult, Task, TaskFactory};
+use crate::engine::{Engine, EngineConfig};
+
+thread_local! {
+    /// paths of templates being compiled, template including itself is compiled when it runs
+    static COMPILING: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
 
 #[derive(Debug)]
 pub struct TemplateFactory {}
@@ -25,7 +34,7 @@ impl TaskFactory for TemplateFactory {
             .and_then(|q| q.as_mapping())
             .iter()
             .flat_map(|q| q.iter())
-            .flat_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+            .flat_map(|(k, v)| Some((k.as_str()?.to_string(), Expr::parse(v.as_str()?))))
             .collect();
 
         let headers = task_root
@@ -33,13 +42,10 @@ impl TaskFactory for TemplateFactory {
             .and_then(|q| q.as_mapping())
             .iter()
             .flat_map(|q| q.iter())
-            .flat_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+            .flat_map(|(k, v)| Some((k.as_str()?.to_string(), Expr::parse(v.as_str()?))))
             .collect();
 
-        let body = task_root
-            .get("body")
-            .map(|v| v.clone())
-            .unwrap_or(YmlValue::Null);
+        let body = CompiledValue::from_yml(task_root.get("body").unwrap_or(&YmlValue::Null));
 
         let result = task_root
             .get("result")
@@ -48,12 +54,15 @@ impl TaskFactory for TemplateFactory {
 
         Some(Box::new(Template {
             name: task_name.to_string(),
+            path_expr: Expr::parse(&format!("${{dsl}}/{}", template_path)),
             template_path,
             next_task,
             query,
             body,
             headers,
             result,
+            config: None,
+            compiled: None,
         }))
     }
 }
@@ -67,59 +76,115 @@ impl TemplateFactory {
 #[derive(Debug)]
 struct Template {
     template_path: String,
+    path_expr: Expr,
     name: String,
-    next_task: Option<String>,
-    headers: HashMap<String, String>,
-    query: HashMap<String, String>,
-    body: YmlValue,
+    next_task: Option<usize>,
+    headers: HashMap<String, Expr>,
+    query: HashMap<String, Expr>,
+    body: CompiledValue,
     result: Option<String>,
+    /// config of the calling flow, set when it is compiled
+    config: Option<EngineConfig>,
+    /// templates with static path are compiled together with the calling flow
+    compiled: Option<Result<Arc<Engine>, String>>,
+}
+
+/// templates keep the response envelope, their result is read as `result.response`
+fn template_config(config: &EngineConfig) -> EngineConfig {
+    EngineConfig {
+        wrap_response: true,
+        ..config.clone()
+    }
+}
+
+fn compile(path: &str, config: &EngineConfig) -> Result<Arc<Engine>, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read template {}: {}", path, e))?;
+    let template: YmlValue = serde_yaml_ng::from_str(&content)
+        .map_err(|e| format!("cannot parse template {}: {}", path, e))?;
+    Ok(Arc::new(Engine::from_template(&template, config)))
 }
 
 #[async_trait]
 impl Task for Template {
     async fn execute(&self, context: Context) -> ExecutionResult {
         // TODO: make template format ruuter compatible
-        let evalueated_expr = context
-            .evaluate_expr(&format!("${{dsl}}/{}", self.template_path))
-            .await;
-        let rendered_path = evalueated_expr.as_str().unwrap_or(&self.template_path);
-        let template = std::fs::read_to_string(rendered_path)
-            .ok()
-            .and_then(|s| serde_yaml_ng::from_str(&s).ok())
-            .unwrap_or(YmlValue::Null);
-        let dsl_val = context.evaluate_expr("${dsl}").await;
-        // will never be the value from the unwrap_or "./unittest_dsl here, because the value is always there
-        let dsl_path = dsl_val.as_str().unwrap_or("./unittest_dsl");
-
-        let internal_engine = Engine::from_template(&template, dsl_path);
-
-        let request = self.create_request(&context).await;
-        let result = internal_engine.execute(request).await;
+        let engine = match &self.compiled {
+            Some(compiled) => compiled.clone(),
+            None => self.compile_rendered(&context).await,
+        };
+
+        let result = match engine {
+            Ok(engine) => {
+                let request = self.create_request(&context).await;
+                // boxed, so state of nested templates is not kept on the stack
+                Box::pin(engine.execute(request)).await.0
+            }
+            Err(e) => {
+                warn!("Template task {} failed: {}", self.name, e);
+                json!({"error": e})
+            }
+        };
         if let Some(r) = &self.result {
             context
                 .evaluate_expr(&Context::wrap_js_code(&format!(
                     "let {} = {};",
                     r,
-                    result.0.to_string()
+                    result.to_string()
                 )))
                 .await;
         }
 
-        ExecutionResult(context, self.next_task.clone())
+        ExecutionResult(context, self.next_task)
     }
 
     fn get_name(&self) -> &str {
         return &self.name;
     }
+
+    fn get_type(&self) -> &'static str {
+        "template"
+    }
+
+    /// missing or broken template with static path is reported when the flow is compiled
+    fn prepare(&mut self, config: &EngineConfig) {
+        let config = template_config(config);
+        if !self.template_path.contains("${") {
+            let path = format!("{}/{}", config.dsl_path, self.template_path);
+            if COMPILING.with_borrow_mut(|c| c.insert(path.clone())) {
+                let compiled = compile(&path, &config);
+                if let Err(e) = &compiled {
+                    error!("Template task {}: {}", self.name, e);
+                }
+                self.compiled = Some(compiled);
+                COMPILING.with_borrow_mut(|c| c.remove(&path));
+            }
+        }
+        self.config = Some(config);
+    }
 }
 
 impl Template {
+    /// rendered paths and templates including themselves are compiled on every run
+    async fn compile_rendered(&self, context: &Context) -> Result<Arc<Engine>, String> {
+        let evalueated_expr = context.evaluate(&self.path_expr).await;
+        let rendered_path = evalueated_expr.as_str().unwrap_or(&self.template_path);
+        let config = match &self.config {
+            Some(config) => config.clone(),
+            None => {
+                let dsl_val = context.evaluate_expr("${dsl}").await;
+                template_config(&EngineConfig::new(dsl_val.as_str().unwrap_or_default()))
+            }
+        };
+        compile(rendered_path, &config)
+    }
+
     async fn create_request(&self, context: &Context) -> Request {
-        let body = render_obj(&self.body, context).await;
+        let body = self.body.render(context).await;
         let headers = join_all(
             self.headers
                 .iter()
-                .map(async |(k, v)| (k.to_string(), context.evaluate_expr(v).await)),
+                .map(async |(k, v)| (k.to_string(), context.evaluate(v).await)),
         )
         .await
         .into_iter()
@@ -129,14 +194,14 @@ impl Template {
         let query = join_all(
             self.query
                 .iter()
-                .map(async |(k, v)| (k.to_string(), context.evaluate_expr(v).await)),
+                .map(async |(k, v)| (k.to_string(), context.evaluate(v).await)),
         )
         .await
         .into_iter()
         .flat_map(|(k, v)| Some((k, v.as_str()?.to_string())))
         .collect();
 
-        Request::new(headers, body, query)
+        Request::new(headers, body, query).with_request_id(context.request_id.clone())
     }
 }
 
@@ -145,6 +210,7 @@ mod test {
     use crate::{
         endpoints::types::Request,
         engine::{
+            EngineConfig,
             context::Context,
             tasks::{task::TaskFactory, template::TemplateFactory},
         },
@@ -168,6 +234,35 @@ mod test {
         assert!(obj.is_none());
     }
 
+    #[tokio::test]
+    async fn test_missing_template() {
+        let factory = TemplateFactory::new();
+        let mut task = factory
+            .from_yml(
+                "test",
+                &serde_yaml_ng::from_str(
+                    r#"
+                        test:
+                          template: test/TEMPLATES/not_exists.yml
+                          result: res
+                    "#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        task.prepare(&EngineConfig::new("./unittest_dsl"));
+
+        let context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let context = task.execute(context).await.0;
+
+        let res = context.evaluate_expr("${res.error}").await;
+        assert!(
+            res.as_str()
+                .unwrap()
+                .starts_with("cannot read template ./unittest_dsl/test/TEMPLATES/not_exists.yml")
+        );
+    }
+
     #[tokio::test]
     async fn test_template_task() {
         let factory = TemplateFactory::new();
@@ -192,7 +287,8 @@ mod test {
         assert!(obj.is_some());
         let task = obj.unwrap();
 
-        let context = Context::from_request(Request::default(), "./unittest_dsl").await;
+        let request = Request::default().with_request_id(Some("req-1".to_string()));
+        let context = Context::from_request(request, "./unittest_dsl").await;
 
         let res = task.execute(context).await;
         let context = res.0;
@@ -202,9 +298,21 @@ mod test {
         assert_eq!(
             *res.get("response").unwrap(),
             json!({
+                "method": "",
+                "route": "",
+                "rawPath": "",
+                "rawQuery": "",
+                "path": {},
+                "params": {"test": "ok"},
+                "paramsAll": {},
                 "headers": {"test": "ok"},
+                "headersAll": {},
+                "cookies": {},
+                "remoteAddr": null,
+                "remoteIp": null,
+                "requestId": "req-1",
                 "body": {"test": "ok"},
-                "params": {"test": "ok"}
+                "rawBody": null
             })
         );
     }
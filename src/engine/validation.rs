@@ -0,0 +1,573 @@
+use serde::Serialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use serde_yaml_ng::Value as YmlValue;
+
+use crate::endpoints::parser::Endpoint;
+use crate::endpoints::types::Request;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub field: String,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    required: bool,
+    schema: Schema,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Schema {
+    kind: String,
+    enum_values: Vec<JsonValue>,
+    fields: Option<Vec<Field>>,
+    items: Option<Box<Schema>>,
+}
+
+fn parse_fields(seq: &[YmlValue]) -> Vec<Field> {
+    seq.iter()
+        .flat_map(|f| {
+            Some(Field {
+                name: f.get("field")?.as_str()?.to_string(),
+                required: f.get("required").and_then(|r| r.as_bool()).unwrap_or(false),
+                schema: Schema::from_yml(f),
+            })
+        })
+        .collect()
+}
+
+/// enum of a string value matches numbers written without quotes too
+fn enum_value_str(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl Schema {
+    fn from_yml(yml: &YmlValue) -> Self {
+        // sequence is a shortcut for fields of an object
+        if let Some(seq) = yml.as_sequence() {
+            return Self {
+                kind: "object".into(),
+                fields: Some(parse_fields(seq)),
+                ..Default::default()
+            };
+        }
+
+        Self {
+            kind: yml
+                .get("type")
+                .and_then(|t| t.as_str())
+                .unwrap_or("string")
+                .to_string(),
+            enum_values: yml
+                .get("enum")
+                .and_then(|e| e.as_sequence())
+                .iter()
+                .flat_map(|e| e.iter())
+                .flat_map(|v| serde_json::to_value(v).ok())
+                .collect(),
+            fields: yml
+                .get("fields")
+                .and_then(|f| f.as_sequence())
+                .map(|f| parse_fields(f)),
+            items: yml.get("items").map(|i| Box::new(Self::from_yml(i))),
+        }
+    }
+
+    /// path, query and header values always come as strings
+    fn check_str(&self, path: &str, value: &str, acc: &mut Vec<Violation>) {
+        let is_type_ok = match self.kind.as_str() {
+            "number" => value.parse::<f64>().is_ok(),
+            "integer" => value.parse::<i64>().is_ok(),
+            "boolean" => value == "true" || value == "false",
+            _ => true,
+        };
+        if !is_type_ok {
+            acc.push(Violation::new(path, &format!("expected {}", self.kind)));
+            return;
+        }
+
+        if !self.enum_values.is_empty()
+            && !self.enum_values.iter().any(|e| enum_value_str(e) == value)
+        {
+            acc.push(Violation::new(path, "value is not allowed"));
+        }
+    }
+
+    fn check_json(&self, path: &str, value: &mut JsonValue, strip: bool, acc: &mut Vec<Violation>) {
+        let is_type_ok = match self.kind.as_str() {
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            _ => true,
+        };
+        if !is_type_ok {
+            acc.push(Violation::new(path, &format!("expected {}", self.kind)));
+            return;
+        }
+
+        if !self.enum_values.is_empty() && !self.enum_values.contains(value) {
+            acc.push(Violation::new(path, "value is not allowed"));
+            return;
+        }
+
+        match value {
+            JsonValue::Object(obj) => {
+                if let Some(fields) = &self.fields {
+                    check_object(path, fields, obj, strip, acc);
+                }
+            }
+            JsonValue::Array(items) => {
+                if let Some(schema) = &self.items {
+                    for (i, item) in items.iter_mut().enumerate() {
+                        schema.check_json(&format!("{}[{}]", path, i), item, strip, acc);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// object without declared fields accepts anything
+fn check_object(
+    path: &str,
+    fields: &[Field],
+    obj: &mut JsonMap<String, JsonValue>,
+    strip: bool,
+    acc: &mut Vec<Violation>,
+) {
+    for field in fields {
+        let field_path = format!("{}.{}", path, field.name);
+        match obj.get_mut(&field.name) {
+            Some(v) if !v.is_null() => field.schema.check_json(&field_path, v, strip, acc),
+            _ if field.required => acc.push(Violation::new(&field_path, "field is required")),
+            _ => {}
+        }
+    }
+
+    let unknown: Vec<String> = obj
+        .keys()
+        .filter(|k| !fields.iter().any(|f| &f.name == *k))
+        .cloned()
+        .collect();
+    for name in unknown {
+        if strip {
+            obj.remove(&name);
+        } else {
+            acc.push(Violation::new(
+                &format!("{}.{}", path, name),
+                "field is not allowed",
+            ));
+        }
+    }
+}
+
+/// request part of the merged `declare` tasks of an endpoint and its guards
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    path: Vec<Field>,
+    params: Vec<Field>,
+    headers: Vec<Field>,
+    body: Option<Schema>,
+}
+
+fn has_allowlist(yml: &YmlValue) -> bool {
+    yml.as_mapping()
+        .iter()
+        .flat_map(|m| m.values())
+        .filter(|t| t.get("call").and_then(|c| c.as_str()) == Some("declare"))
+        .any(|t| t.get("allowlist").is_some())
+}
+
+impl Allowlist {
+    /// endpoints without any `allowlist` are not validated
+    pub fn from_endpoint(endpoint: &Endpoint) -> Option<Self> {
+        let is_declared = has_allowlist(&endpoint.yml_content)
+            || endpoint
+                .guards
+                .iter()
+                .any(|g| has_allowlist(&g.yml_content));
+        if !is_declared {
+            return None;
+        }
+
+        let declaration: YmlValue = serde_yaml_ng::from_str(&endpoint.merged_declaration).ok()?;
+        Some(Self::from_yml(
+            declaration.get("declaration")?.get("allowlist")?,
+        ))
+    }
+
+    fn from_yml(yml: &YmlValue) -> Self {
+        let fields = |key: &str| {
+            yml.get(key)
+                .and_then(|f| f.as_sequence())
+                .map(|f| parse_fields(f))
+                .unwrap_or_default()
+        };
+
+        Self {
+            path: fields("path"),
+            params: fields("params"),
+            headers: fields("headers"),
+            body: yml
+                .get("body")
+                .filter(|b| !b.is_null())
+                .map(Schema::from_yml),
+        }
+    }
+
+    /// unknown headers are always accepted, unknown params and body fields
+    /// are rejected or removed with `strip`
+    pub fn check(&self, request: &mut Request, strip: bool) -> Result<(), Vec<Violation>> {
+        let mut acc = Vec::new();
+
+        for field in &self.path {
+            if let Some(value) = request.path_params().get(&field.name) {
+                field
+                    .schema
+                    .check_str(&format!("path.{}", field.name), value, &mut acc);
+            }
+        }
+
+        for field in &self.params {
+            let field_path = format!("params.{}", field.name);
+            let values = request.param_values(&field.name);
+            if values.is_empty() && field.required {
+                acc.push(Violation::new(&field_path, "field is required"));
+            }
+            for value in values {
+                field.schema.check_str(&field_path, value, &mut acc);
+            }
+        }
+        let unknown: Vec<String> = request
+            .params()
+            .keys()
+            .filter(|k| !self.params.iter().any(|f| &f.name == *k))
+            .cloned()
+            .collect();
+        for name in unknown {
+            if strip {
+                request.remove_param(&name);
+            } else {
+                acc.push(Violation::new(
+                    &format!("params.{}", name),
+                    "field is not allowed",
+                ));
+            }
+        }
+
+        for field in &self.headers {
+            let field_path = format!("headers.{}", field.name);
+            let values = request.header_values(&field.name.to_lowercase());
+            if values.is_empty() && field.required {
+                acc.push(Violation::new(&field_path, "field is required"));
+            }
+            for value in values {
+                field.schema.check_str(&field_path, value, &mut acc);
+            }
+        }
+
+        if let Some(schema) = &self.body {
+            let original = strip.then(|| request.body_mut().clone());
+            let body = request.body_mut();
+            match (&schema.fields, body.is_null()) {
+                // missing body is an empty object
+                (Some(fields), true) => {
+                    check_object("body", fields, &mut JsonMap::new(), strip, &mut acc)
+                }
+                _ => schema.check_json("body", body, strip, &mut acc),
+            }
+
+            // flows must not read stripped fields from raw body
+            if original.is_some_and(|o| &o != request.body_mut()) {
+                request.sync_raw_body();
+            }
+        }
+
+        if !acc.is_empty() {
+            return Err(acc);
+        }
+
+        Ok(())
+    }
+}
+
+/// `responses` of the merged `declare` tasks, returned values are checked against them in dev mode
+#[derive(Debug, Clone, Default)]
+pub struct ResponseSchemas {
+    pub endpoint: String,
+    responses: Vec<(String, Option<Schema>)>,
+}
+
+impl ResponseSchemas {
+    pub fn from_endpoint(endpoint: &Endpoint) -> Option<Self> {
+        let declaration: YmlValue = serde_yaml_ng::from_str(&endpoint.merged_declaration).ok()?;
+        let responses = declaration
+            .get("declaration")?
+            .get("responses")?
+            .as_mapping()?;
+        if responses.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            endpoint: endpoint.to_string(),
+            responses: responses
+                .iter()
+                .flat_map(|(status, response)| {
+                    let body = response.get("body").filter(|b| !b.is_null());
+                    Some((status.as_str()?.to_string(), body.map(Schema::from_yml)))
+                })
+                .collect(),
+        })
+    }
+
+    /// response without declared body is not checked
+    pub fn check(&self, status: u16, value: &JsonValue) -> Vec<Violation> {
+        let status = status.to_string();
+        let Some((_, schema)) = self.responses.iter().find(|(s, _)| *s == status) else {
+            return vec![Violation::new("status", "status is not declared")];
+        };
+        let Some(schema) = schema else {
+            return vec![];
+        };
+
+        let mut acc = Vec::new();
+        schema.check_json("response", &mut value.clone(), false, &mut acc);
+        acc
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use serde_json::{Value as JsonValue, json};
+
+    use crate::endpoints::parser::{Endpoint, Guard};
+    use crate::endpoints::types::Request;
+    use crate::engine::validation::{Allowlist, ResponseSchemas, Violation};
+    use axum::{body::Body, extract::Request as AxumRequest};
+
+    fn allowlist() -> Allowlist {
+        Allowlist::from_yml(
+            &serde_yaml_ng::from_str(
+                r#"
+                    params:
+                      - field: limit
+                        type: integer
+                      - field: sort
+                        enum: [asc, desc]
+                    headers:
+                      - field: X-Tenant
+                        required: true
+                    body:
+                      type: object
+                      fields:
+                        - field: name
+                          type: string
+                          required: true
+                        - field: tags
+                          type: array
+                          items:
+                            type: string
+                        - field: kind
+                          type: number
+                          enum: [1, 2]
+                "#,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn request(params: &[(&str, &str)], body: JsonValue) -> Request {
+        Request::new(
+            HashMap::from([("x-tenant".to_string(), "t1".to_string())]),
+            body,
+            params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    fn fields(violations: Vec<Violation>) -> Vec<String> {
+        let mut fields: Vec<String> = violations.into_iter().map(|v| v.field).collect();
+        fields.sort();
+        fields
+    }
+
+    #[test]
+    fn test_valid_request() {
+        let mut req = request(
+            &[("limit", "10"), ("sort", "asc")],
+            json!({"name": "a", "tags": ["x"], "kind": 2}),
+        );
+        assert!(allowlist().check(&mut req, false).is_ok());
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut req = request(
+            &[("limit", "ten"), ("sort", "up"), ("page", "1")],
+            json!({"tags": ["x", 1], "kind": 3, "extra": true}),
+        );
+        let violations = allowlist().check(&mut req, false).unwrap_err();
+        assert_eq!(
+            fields(violations),
+            vec![
+                "body.extra",
+                "body.kind",
+                "body.name",
+                "body.tags[1]",
+                "params.limit",
+                "params.page",
+                "params.sort",
+            ]
+        );
+
+        let mut req = Request::new(HashMap::new(), JsonValue::Null, HashMap::new());
+        let violations = allowlist().check(&mut req, false).unwrap_err();
+        assert_eq!(fields(violations), vec!["body.name", "headers.X-Tenant"]);
+    }
+
+    #[test]
+    fn test_strip_unknown_fields() {
+        let mut req = request(
+            &[("limit", "1"), ("page", "1")],
+            json!({"name": "a", "extra": true}),
+        );
+        assert!(allowlist().check(&mut req, true).is_ok());
+        assert_eq!(req.params().len(), 1);
+        assert_eq!(req.body_mut(), &json!({"name": "a"}));
+    }
+
+    #[tokio::test]
+    async fn test_raw_request_parts() {
+        let raw = |query: &str, body: &str| {
+            AxumRequest::builder()
+                .uri(format!("/some?{}", query))
+                .header("x-tenant", "t1")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let mut req = Request::from_request(raw(
+            "limit=1&page=2&sort=asc",
+            r#"{"name": "a", "extra": true}"#,
+        ))
+        .await;
+        assert!(allowlist().check(&mut req, true).is_ok());
+        let incoming = serde_json::to_value(&req).unwrap();
+        assert_eq!(incoming["rawQuery"], json!("limit=1&sort=asc"));
+        assert_eq!(
+            incoming["paramsAll"],
+            json!({"limit": ["1"], "sort": ["asc"]})
+        );
+        assert_eq!(incoming["rawBody"], json!(r#"{"name":"a"}"#));
+
+        // every value of repeated param is checked
+        let mut req = Request::from_request(raw("limit=1&limit=x", r#"{"name": "a"}"#)).await;
+        let violations = allowlist().check(&mut req, true).unwrap_err();
+        assert_eq!(fields(violations), vec!["params.limit"]);
+    }
+
+    #[test]
+    fn test_from_endpoint() {
+        let mut endpoint = Endpoint {
+            guards: vec![],
+            tag: "some".to_string(),
+            url_path: "/some/".to_string(),
+            method: rstmytype::ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                    test:
+                      return: ok
+                "#,
+            )
+            .unwrap(),
+            merged_declaration: "".into(),
+            websocket: false,
+            source: "".into(),
+        };
+        assert!(Allowlist::from_endpoint(&endpoint).is_none());
+
+        endpoint.guards.push(Guard {
+            yml_content: serde_yaml_ng::from_str(
+                r#"
+                    declaration:
+                      call: declare
+                      allowlist:
+                        params:
+                          - field: limit
+                "#,
+            )
+            .unwrap(),
+            source: "".into(),
+        });
+        endpoint.merged_declaration = r#"
+            declaration:
+              call: declare
+              allowlist:
+                params:
+                  - field: limit
+                body: null
+        "#
+        .into();
+        let allowlist = Allowlist::from_endpoint(&endpoint).unwrap();
+        assert_eq!(allowlist.params.len(), 1);
+        assert!(allowlist.body.is_none());
+    }
+
+    #[test]
+    fn test_response_schemas() {
+        let endpoint = Endpoint {
+            guards: vec![],
+            tag: "some".to_string(),
+            url_path: "/some/".to_string(),
+            method: rstmytype::ApiEndpointMethod::Get,
+            yml_content: serde_yaml_ng::Value::Null,
+            merged_declaration: r#"
+                declaration:
+                  call: declare
+                  responses:
+                    "200":
+                      body:
+                        - field: id
+                          type: number
+                          required: true
+                    "204": {}
+            "#
+            .into(),
+            websocket: false,
+            source: "".into(),
+        };
+
+        let schemas = ResponseSchemas::from_endpoint(&endpoint).unwrap();
+        assert!(schemas.check(200, &json!({"id": 1})).is_empty());
+        assert!(schemas.check(204, &JsonValue::Null).is_empty());
+        assert_eq!(
+            fields(schemas.check(200, &json!({"id": "1", "x": 2}))),
+            vec!["response.id", "response.x"]
+        );
+        assert_eq!(fields(schemas.check(500, &JsonValue::Null)), vec!["status"]);
+    }
+}
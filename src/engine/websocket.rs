@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::response::{IntoResponse, Response};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use futures::{SinkExt, StreamExt};
+use log::{debug, warn};
+use serde_json::Value as JsonValue;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::endpoints::types::Request;
+use crate::engine::Engine;
+use crate::engine::context::{Context, OUTGOING_BUFFER, SocketMessage};
+
+/// text frames holding json are parsed, any other text is passed as string,
+/// binary frames are passed as base64 string
+fn message_to_json(message: Message) -> Option<JsonValue> {
+    match message {
+        Message::Text(text) => Some(
+            serde_json::from_str(text.as_str())
+                .unwrap_or_else(|_| JsonValue::String(text.to_string())),
+        ),
+        Message::Binary(bytes) => Some(JsonValue::String(BASE64.encode(bytes))),
+        _ => None,
+    }
+}
+
+fn socket_to_message(message: SocketMessage) -> Message {
+    match message {
+        SocketMessage::Send(JsonValue::String(s)) => Message::Text(s.into()),
+        SocketMessage::Send(other) => Message::Text(other.to_string().into()),
+        SocketMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+            code: code.unwrap_or(1000),
+            reason: reason.into(),
+        })),
+    }
+}
+
+async fn forward_outgoing(
+    mut receiver: Receiver<SocketMessage>,
+    mut sink: futures::stream::SplitSink<WebSocket, Message>,
+) {
+    while let Some(message) = receiver.recv().await {
+        let is_close = matches!(message, SocketMessage::Close(..));
+        if sink.send(socket_to_message(message)).await.is_err() {
+            debug!("Websocket client is gone");
+            break;
+        }
+        if is_close {
+            break;
+        }
+    }
+}
+
+impl Engine {
+    /// guards are executed before upgrade, so they still can reject the request.
+    /// Flow is executed for every incoming message in the same js context,
+    /// message is available as `message` variable
+    pub async fn execute_websocket(
+        self: Arc<Self>,
+        upgrade: WebSocketUpgrade,
+        request: Request,
+    ) -> Response {
+        let request = match self.check_request(request) {
+            Ok(r) => r,
+            Err(response) => return response.into_response(),
+        };
+        let context = Context::from_request(request, &self.config.dsl_path).await;
+        let budget = self.budget();
+        let guarded = self.run_guards(context, &budget);
+        let context = match budget.limit(guarded).await {
+            Ok(Ok(c)) => c,
+            Ok(Err(response)) => return response.into_response(),
+            Err(abort) => return abort.to_response().into_response(),
+        };
+
+        upgrade.on_upgrade(move |socket| self.serve_websocket(socket, context))
+    }
+
+    async fn serve_websocket(self: Arc<Self>, socket: WebSocket, mut context: Context) {
+        let (sink, stream) = socket.split();
+        let (sender, receiver) = tokio::sync::mpsc::channel(OUTGOING_BUFFER);
+        context.set_socket_sender(sender.clone());
+
+        let writer = tokio::spawn(forward_outgoing(receiver, sink));
+
+        // context and sender are dropped on return, so writer can finish
+        self.serve_messages(stream, context, sender).await;
+        if let Err(e) = writer.await {
+            warn!("Websocket writer failed: {}", e);
+        }
+    }
+
+    /// every message gets its own step and time budget,
+    /// socket is closed when a flow is aborted as its context is lost
+    async fn serve_messages(
+        &self,
+        mut stream: futures::stream::SplitStream<WebSocket>,
+        mut context: Context,
+        sender: Sender<SocketMessage>,
+    ) {
+        while let Some(Ok(message)) = stream.next().await {
+            if matches!(message, Message::Close(_)) {
+                break;
+            }
+            let Some(message) = message_to_json(message) else {
+                continue;
+            };
+
+            context.set_variable("message", &message).await;
+            context.set_return_value(200, JsonValue::Null);
+            let budget = self.budget();
+            let flow = self.tree.walk_through(context, &budget);
+            context = match budget.limit(flow).await.and_then(|r| r) {
+                Ok(context) => context,
+                Err(abort) => {
+                    sender.send(SocketMessage::Send(abort.to_json())).await.ok();
+                    sender
+                        .send(SocketMessage::Close(Some(1011), "flow aborted".to_string()))
+                        .await
+                        .ok();
+                    return;
+                }
+            };
+
+            let return_value = context.get_return_value();
+            if !return_value.json.is_null() {
+                sender
+                    .send(SocketMessage::Send(return_value.json))
+                    .await
+                    .ok();
+            }
+
+            if sender.is_closed() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::context::SocketMessage;
+    use crate::engine::websocket::{message_to_json, socket_to_message};
+    use axum::extract::ws::Message;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_to_json() {
+        assert_eq!(
+            message_to_json(Message::Text(r#"{"a": 1}"#.into())),
+            Some(json!({"a": 1}))
+        );
+        assert_eq!(
+            message_to_json(Message::Text("hello".into())),
+            Some(json!("hello"))
+        );
+        assert_eq!(
+            message_to_json(Message::Binary(vec![0xff, 0xfe].into())),
+            Some(json!("//4="))
+        );
+        assert_eq!(message_to_json(Message::Ping(vec![].into())), None);
+    }
+
+    #[test]
+    fn test_socket_to_message() {
+        assert_eq!(
+            socket_to_message(SocketMessage::Send(json!("hi"))),
+            Message::Text("hi".into())
+        );
+        assert_eq!(
+            socket_to_message(SocketMessage::Send(json!({"a": 1}))),
+            Message::Text(r#"{"a":1}"#.into())
+        );
+    }
+}
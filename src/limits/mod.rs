@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+/// tasks one request may execute when `--max-steps` is not set
+pub const DEFAULT_MAX_STEPS: usize = 10000;
+
+/// every nested template grows the stack of the request
+pub const MAX_TEMPLATE_DEPTH: usize = 8;
+
+/// `500ms`, `30s` or plain number of seconds, e.g. `1.5`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, scale) = match value.strip_suffix("ms") {
+        Some(ms) => (ms, 0.001),
+        None => (value.strip_suffix('s').unwrap_or(value), 1.0),
+    };
+
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|n| Duration::try_from_secs_f64(n * scale).ok())
+        .filter(|d| !d.is_zero())
+        .ok_or_else(|| format!("Invalid duration: {}", value))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::limits::parse_duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+}
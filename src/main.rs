@@ -1,25 +1,36 @@
+use std::net::SocketAddr;
+use std::process::ExitCode;
 use std::time::Instant;
 
-use axum::{Router, extract::Request, middleware::Next, response::Response};
+use axum::Router;
 use log::{LevelFilter, info, warn};
 use log4rs;
-use log4rs::append::console::ConsoleAppender;
+use log4rs::append::console::{ConsoleAppender, Target};
 use log4rs::config::{Appender, Config, Root};
 use tokio;
 
-use crate::endpoints::load_dsl_endpoints;
+use crate::admin::{AdminState, serve_admin};
+use crate::endpoints::reload::ReloadableRouter;
+use crate::engine::lint::strict_check;
 
+mod access_log;
+mod admin;
 mod args;
+mod commands;
 mod endpoints;
 mod engine;
+mod limits;
+mod metrics;
+mod telemetry;
 
-fn init_logging(args: &args::types::Args) -> Option<()> {
+/// subcommands log to stderr, their output goes to stdout
+fn init_logging(args: &args::types::Args, target: Target) -> Option<()> {
     match &args.log_config {
         Some(path) => {
             log4rs::init_file(path, Default::default()).ok()?;
         }
         None => {
-            let stdout = ConsoleAppender::builder().build();
+            let stdout = ConsoleAppender::builder().target(target).build();
 
             let config = Config::builder()
                 .appender(Appender::builder().build("stdout", Box::new(stdout)))
@@ -42,54 +53,87 @@ fn print_hello() {
     println!(r"                                               ");
 }
 
-async fn uri_middleware(request: Request, next: Next) -> Response {
-    let uri = request.uri().clone();
-
-    let response = next.run(request).await;
-
-    info!("{} - {}", uri, response.status());
-
-    response
-}
-
-async fn init_and_run(args: &args::types::Args) {
+async fn init_and_run(args: &args::types::Args) -> ExitCode {
     let start = Instant::now();
 
-    if init_logging(&args).is_none() {
+    if init_logging(&args, Target::Stdout).is_none() {
         println!("cannot initialize logging!");
-        return;
+        return ExitCode::FAILURE;
     }
 
     print_hello();
 
-    let app = Router::new().layer(axum::middleware::from_fn(uri_middleware));
-    let app = load_dsl_endpoints(&args, app);
+    if args.strict
+        && let Err(e) = strict_check(&args.dsl_path)
+    {
+        warn!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let tracer_provider = telemetry::init_tracing(args);
+
+    let reloadable = ReloadableRouter::new(args);
+    let _watcher = if args.hot_reload {
+        reloadable.watch()
+    } else {
+        None
+    };
+    let admin = AdminState::new(args, reloadable.clone());
+    let app = reloadable.into_router(Router::new());
 
     let listener = match tokio::net::TcpListener::bind(format!("{}:{}", args.bind, args.port)).await
     {
         Ok(l) => l,
         Err(e) => {
             warn!("{}", e);
-            return;
+            return ExitCode::FAILURE;
         }
     };
 
+    admin.set_ready();
+    tokio::spawn(serve_admin(admin));
+
     let duration = start.elapsed();
     info!("Server startup completed in {:?}", duration);
     info!("Starting server at http://{}:{}", args.bind, args.port);
 
-    if let Err(e) = axum::serve(listener, app).await {
+    if let Err(e) = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    {
         warn!("{}", e);
     }
+
+    if let Some(provider) = tracer_provider
+        && let Err(e) = provider.shutdown()
+    {
+        warn!("cannot flush traces: {}", e);
+    }
+
+    ExitCode::SUCCESS
 }
 
-fn main() {
+fn main() -> ExitCode {
     let args = args::types::get_args();
+
+    if let Some(command) = &args.command {
+        if init_logging(&args, Target::Stderr).is_none() {
+            eprintln!("cannot initialize logging!");
+            return ExitCode::FAILURE;
+        }
+        return commands::run_command(&args, command);
+    }
+
     match tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
     {
         Ok(r) => r.block_on(async move { init_and_run(&args).await }),
-        Err(e) => println!("{}", e),
+        Err(e) => {
+            println!("{}", e);
+            ExitCode::FAILURE
+        }
     }
 }
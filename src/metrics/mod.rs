@@ -0,0 +1,249 @@
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::warn;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+/// route label of requests not matched by any DSL route (static files, 404)
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    tasks: IntCounterVec,
+    task_duration: HistogramVec,
+    upstream_requests: IntCounterVec,
+    upstream_errors: IntCounterVec,
+    upstream_duration: HistogramVec,
+}
+
+fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counter =
+        IntCounterVec::new(Opts::new(name, help), labels).expect("metric options are valid");
+    registry
+        .register(Box::new(counter.clone()))
+        .expect("metric is registered once");
+    counter
+}
+
+fn histogram(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> HistogramVec {
+    let histogram = HistogramVec::new(HistogramOpts::new(name, help), labels)
+        .expect("metric options are valid");
+    registry
+        .register(Box::new(histogram.clone()))
+        .expect("metric is registered once");
+    histogram
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("rstrouter".to_string()), None)
+            .expect("registry prefix is valid");
+
+        Self {
+            requests: counter(
+                &registry,
+                "http_requests_total",
+                "Incoming requests",
+                &["method", "route", "status"],
+            ),
+            request_duration: histogram(
+                &registry,
+                "http_request_duration_seconds",
+                "Incoming request latency",
+                &["method", "route", "status"],
+            ),
+            tasks: counter(
+                &registry,
+                "task_executions_total",
+                "Executed DSL tasks",
+                &["task"],
+            ),
+            task_duration: histogram(
+                &registry,
+                "task_duration_seconds",
+                "DSL task execution time",
+                &["task"],
+            ),
+            upstream_requests: counter(
+                &registry,
+                "upstream_requests_total",
+                "Outbound requests of http tasks",
+                &["method", "host", "status"],
+            ),
+            upstream_errors: counter(
+                &registry,
+                "upstream_errors_total",
+                "Outbound requests of http tasks failed without response",
+                &["method", "host"],
+            ),
+            upstream_duration: histogram(
+                &registry,
+                "upstream_request_duration_seconds",
+                "Outbound request latency of http tasks",
+                &["method", "host"],
+            ),
+            registry,
+        }
+    }
+
+    pub fn observe_task(&self, task: &str, duration: Duration) {
+        self.tasks.with_label_values(&[task]).inc();
+        self.task_duration
+            .with_label_values(&[task])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// `status` is None when upstream did not respond
+    pub fn observe_upstream(
+        &self,
+        method: &str,
+        host: &str,
+        status: Option<u16>,
+        duration: Duration,
+    ) {
+        self.upstream_duration
+            .with_label_values(&[method, host])
+            .observe(duration.as_secs_f64());
+
+        match status {
+            Some(status) => self
+                .upstream_requests
+                .with_label_values(&[method, host, &status.to_string()])
+                .inc(),
+            None => self
+                .upstream_errors
+                .with_label_values(&[method, host])
+                .inc(),
+        }
+    }
+
+    fn observe_request(&self, method: &str, route: &str, status: u16, duration: Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.requests.with_label_values(&labels).inc();
+        self.request_duration
+            .with_label_values(&labels)
+            .observe(duration.as_secs_f64());
+    }
+
+    fn encode(&self) -> Option<String> {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            warn!("cannot encode metrics: {}", e);
+            return None;
+        }
+
+        String::from_utf8(buffer).ok()
+    }
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// has to be layered on the router with DSL routes to see matched route
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let response = next.run(request).await;
+
+    METRICS.observe_request(&method, &route, response.status().as_u16(), start.elapsed());
+    response
+}
+
+pub async fn metrics_handler() -> Response {
+    match METRICS.encode() {
+        Some(body) => (
+            [(
+                header::CONTENT_TYPE,
+                TextEncoder::new().format_type().to_string(),
+            )],
+            body,
+        )
+            .into_response(),
+        None => axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::Request,
+        routing::get,
+    };
+    use tower::ServiceExt;
+
+    use crate::metrics::{METRICS, metrics_handler, track_requests};
+
+    async fn scrape() -> String {
+        let resp = metrics_handler().await;
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_track_requests() {
+        let app = Router::new()
+            .route("/metrics-test/{id}", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(track_requests));
+
+        let req = Request::builder()
+            .uri("/metrics-test/12")
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap();
+        let req = Request::builder()
+            .uri("/metrics-missing")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(req).await.unwrap();
+
+        let metrics = scrape().await;
+        assert!(metrics.contains(
+            r#"rstrouter_http_requests_total{method="GET",route="/metrics-test/{id}",status="200"}"#
+        ));
+        assert!(metrics.contains(
+            r#"rstrouter_http_requests_total{method="GET",route="unmatched",status="404"}"#
+        ));
+        assert!(metrics.contains("rstrouter_http_request_duration_seconds_bucket"));
+    }
+
+    #[tokio::test]
+    async fn test_observe_task_and_upstream() {
+        METRICS.observe_task("metrics-test", Duration::from_millis(3));
+        METRICS.observe_upstream(
+            "GET",
+            "metrics-test.local",
+            Some(502),
+            Duration::from_millis(5),
+        );
+        METRICS.observe_upstream("GET", "metrics-test.local", None, Duration::from_millis(5));
+
+        let metrics = scrape().await;
+        assert!(metrics.contains(r#"rstrouter_task_executions_total{task="metrics-test"} 1"#));
+        assert!(metrics.contains(
+            r#"rstrouter_upstream_requests_total{host="metrics-test.local",method="GET",status="502"} 1"#
+        ));
+        assert!(metrics.contains(
+            r#"rstrouter_upstream_errors_total{host="metrics-test.local",method="GET"} 1"#
+        ));
+    }
+}
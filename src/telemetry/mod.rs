@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use log::warn;
+use opentelemetry::{
+    global,
+    trace::{TraceContextExt as _, TracerProvider as _},
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tracing::{Span, Subscriber, info_span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{Layer, filter::filter_fn, layer::SubscriberExt, registry::LookupSpan};
+
+use crate::args::types::Args;
+use crate::endpoints::types::Request;
+
+const SERVICE_NAME: &str = "rstrouter";
+const TRACES_PATH: &str = "/v1/traces";
+
+/// endpoint is the collector base url, same as `OTEL_EXPORTER_OTLP_ENDPOINT`
+fn traces_endpoint(endpoint: &str) -> String {
+    format!("{}{}", endpoint.trim_end_matches('/'), TRACES_PATH)
+}
+
+/// spans are always recorded so `traceparent` is propagated,
+/// they are exported only when OTLP endpoint is set
+fn tracer_provider(otlp_endpoint: Option<&str>) -> SdkTracerProvider {
+    let mut builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build());
+
+    if let Some(endpoint) = otlp_endpoint {
+        match SpanExporter::builder()
+            .with_http()
+            .with_endpoint(traces_endpoint(endpoint))
+            .build()
+        {
+            Ok(exporter) => builder = builder.with_batch_exporter(exporter),
+            Err(e) => warn!("cannot create OTLP exporter for {}: {}", endpoint, e),
+        }
+    }
+
+    builder.build()
+}
+
+/// only spans of this crate are turned into OpenTelemetry spans
+fn otel_layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(SERVICE_NAME))
+        .with_filter(filter_fn(|m| m.target().starts_with(SERVICE_NAME)))
+}
+
+/// returned provider has to be shut down on exit to flush pending spans
+pub fn init_tracing(args: &Args) -> Option<SdkTracerProvider> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = tracer_provider(args.otlp_endpoint.as_deref());
+    let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| warn!("cannot initialize tracing: {}", e))
+        .ok()?;
+
+    Some(provider)
+}
+
+/// root span of DSL flow, continues trace of incoming `traceparent` if any,
+/// without it the span stays in the current trace, so templates are children of their callers
+pub fn request_span(request: &Request) -> Span {
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {}", request.method(), request.route()),
+        otel.kind = "server",
+        http.request.method = request.method(),
+        http.route = request.route(),
+    );
+
+    let parent = global::get_text_map_propagator(|p| p.extract(request.headers()));
+    if parent.span().span_context().is_valid() {
+        span.set_parent(parent);
+    }
+    span
+}
+
+/// adds `traceparent` of the current span, explicitly declared headers win
+pub fn inject_trace_context(headers: &mut HeaderMap) {
+    let context = Span::current().context();
+    let mut injected: HashMap<String, String> = HashMap::new();
+    global::get_text_map_propagator(|p| p.inject_context(&context, &mut injected));
+
+    for (k, v) in injected {
+        let (Ok(name), Ok(value)) = (HeaderName::try_from(k), HeaderValue::try_from(v)) else {
+            continue;
+        };
+        if !headers.contains_key(&name) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use httpmock::{Method::GET, Method::POST, MockServer};
+    use opentelemetry::global;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use serde_json::{Value as JsonValue, json};
+    use tracing::info_span;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::endpoints::types::Request;
+    use crate::engine::{Engine, EngineConfig};
+    use crate::telemetry::{otel_layer, tracer_provider, traces_endpoint};
+
+    #[test]
+    fn test_traces_endpoint() {
+        assert_eq!(
+            traces_endpoint("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_endpoint("http://collector/otlp/"),
+            "http://collector/otlp/v1/traces"
+        );
+    }
+
+    #[test]
+    fn test_export_to_collector() {
+        let collector = MockServer::start();
+        let mock = collector.mock(|when, then| {
+            when.method(POST)
+                .path("/v1/traces")
+                .header("content-type", "application/x-protobuf");
+            then.status(200);
+        });
+
+        let provider = tracer_provider(Some(&collector.base_url()));
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("export-test");
+            let _entered = span.enter();
+        });
+
+        provider.force_flush().unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_traceparent_propagation() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = tracer_provider(None);
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let upstream = MockServer::start_async().await;
+        let mock = upstream
+            .mock_async(|when, then| {
+                when.method(GET).path("/traced").matches(|req| {
+                    // same trace, parent is the http span
+                    req.headers.iter().flatten().any(|(k, v)| {
+                        k == "traceparent"
+                            && v.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-")
+                            && !v.contains("00f067aa0ba902b7")
+                    })
+                });
+                then.body(json!({"ok": true}).to_string()).status(200);
+            })
+            .await;
+
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(&format!(
+                r#"
+                    call:
+                      call: http.get
+                      args:
+                        url: {}
+                      result: res
+
+                    return:
+                      return: ${{res.response.body.ok}}
+                "#,
+                upstream.url("/traced")
+            ))
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let request = Request::new(
+            HashMap::from([(
+                "traceparent".to_string(),
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+            )]),
+            JsonValue::Null,
+            HashMap::new(),
+        );
+        let res = engine.execute(request).await;
+
+        assert_eq!(res.0, json!({"response": true}));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_template_span_in_caller_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = tracer_provider(None);
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let upstream = MockServer::start_async().await;
+        let mock = upstream
+            .mock_async(|when, then| {
+                when.method(GET).path("/traced").matches(|req| {
+                    req.headers.iter().flatten().any(|(k, v)| {
+                        k == "traceparent" && v.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-")
+                    })
+                });
+                then.body(json!({"ok": true}).to_string()).status(200);
+            })
+            .await;
+
+        let engine = Engine::from_template(
+            &serde_yaml_ng::from_str(&format!(
+                r#"
+                    call:
+                      template: test/TEMPLATES/traced.yml
+                      body:
+                        url: {}
+                      result: res
+
+                    return:
+                      return: ${{res.response}}
+                "#,
+                upstream.url("/traced")
+            ))
+            .unwrap(),
+            &EngineConfig::new("./unittest_dsl"),
+        );
+
+        let request = Request::new(
+            HashMap::from([(
+                "traceparent".to_string(),
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+            )]),
+            JsonValue::Null,
+            HashMap::new(),
+        );
+        let res = engine.execute(request).await;
+
+        assert_eq!(res.0, json!({"response": true}));
+        mock.assert();
+    }
+}